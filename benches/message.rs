@@ -0,0 +1,117 @@
+//! Benchmarks the local work done for every send: applying the service defaults, expanding the
+//! convenience fields and encoding the request body, compared with building the same body as a
+//! `serde_json::Value`.
+//!
+//! Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fcm_notification::{FcmNotification, Message, Priority, ServiceAccount};
+use serde_json::json;
+use std::time::Duration;
+
+/// A service that is never used to send, so its credentials don't need to be valid.
+fn service() -> FcmNotification {
+    let account: ServiceAccount = serde_json::from_value(serde_json::json!({
+        "type": "service_account",
+        "project_id": "bench-project",
+        "private_key_id": "bench-key-id",
+        "private_key": "unused",
+        "client_email": "bench@bench-project.iam.gserviceaccount.com",
+        "client_id": "0",
+        "auth_uri": "https://accounts.google.com/o/oauth2/auth",
+        "token_uri": "https://oauth2.googleapis.com/token",
+        "auth_provider_x509_cert_url": "https://www.googleapis.com/oauth2/v1/certs",
+        "client_x509_cert_url": "https://www.googleapis.com/robot/v1/metadata/x509/bench",
+        "universe_domain": "googleapis.com"
+    }))
+    .unwrap();
+    FcmNotification::from_service_account(account)
+        .unwrap()
+        .with_default_ttl(Duration::from_secs(3600))
+        .with_default_priority(Priority::High)
+}
+
+/// A typical chat notification to one device.
+fn token_message() -> Message {
+    let mut message = Message::notification("device-token", "Alice", "Are we still on for lunch?");
+    message.data = Some(
+        [
+            ("chat_id".to_string(), "42".to_string()),
+            ("sender".to_string(), "alice".to_string()),
+        ]
+        .into(),
+    );
+    message.image = Some("https://example.com/alice.png".to_string());
+    message
+}
+
+/// Builds the body `request_body` encodes for `message` as a `serde_json::Value`.
+fn json_request(message: &Message) -> serde_json::Value {
+    let notification = message.notification.as_ref().unwrap();
+    let data = message.data.clone().unwrap();
+    let image = message.image.as_deref().unwrap();
+    json!({
+        "message": {
+            "token": "device-token",
+            "notification": {
+                "title": notification.title,
+                "body": notification.body,
+                "image": image,
+            },
+            "data": data,
+            "android": {
+                "priority": "HIGH",
+                "ttl": "3600s",
+                "notification": {
+                    "image": image,
+                },
+            },
+            "apns": {
+                "headers": {
+                    "apns-priority": "10",
+                    "apns-expiration": (unix_now() + 3600).to_string(),
+                },
+                "payload": {
+                    "aps": {
+                        "mutable-content": 1,
+                    },
+                },
+                "fcm_options": {
+                    "image": image,
+                },
+            },
+            "webpush": {
+                "headers": {
+                    "Urgency": "high",
+                    "TTL": "3600",
+                },
+            },
+        }
+    })
+}
+
+/// Returns the current Unix time in seconds.
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn bench_message(c: &mut Criterion) {
+    let service = service();
+    let message = token_message();
+
+    c.bench_function("estimate_size", |b| {
+        b.iter(|| service.estimate_size(black_box(&message)))
+    });
+    c.bench_function("request_body", |b| {
+        b.iter(|| service.request_body(black_box(&message)).unwrap())
+    });
+    c.bench_function("json_request_body", |b| {
+        b.iter(|| serde_json::to_vec(&json_request(black_box(&message))).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_message);
+criterion_main!(benches);
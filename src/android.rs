@@ -0,0 +1,322 @@
+//! Android-specific options for messages delivered to Android devices.
+
+use crate::{FcmError, ValidationError};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::ser::Error as _;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Options applied to messages delivered to Android devices.
+///
+/// Serialized as `message.android`.
+///
+/// # Example
+/// ```rust
+/// # use fcm_notification::{AndroidConfig, AndroidMessagePriority};
+/// let android = AndroidConfig {
+///     priority: Some(AndroidMessagePriority::High),
+///     direct_boot_ok: Some(true),
+///     ..AndroidConfig::default()
+/// };
+/// assert_eq!(
+///     serde_json::to_value(&android).unwrap(),
+///     serde_json::json!({ "priority": "HIGH", "direct_boot_ok": true })
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AndroidConfig {
+    /// The delivery priority of the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<AndroidMessagePriority>,
+    /// How long FCM keeps the message if the device is offline. Zero means "deliver now or drop".
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::duration::serialize_option"
+    )]
+    pub ttl: Option<Duration>,
+    /// Identifies a group of messages of which only the latest is delivered once the device
+    /// comes back online, e.g. `score_update`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collapse_key: Option<String>,
+    /// Only deliver the message to the app with this package name, e.g. `com.example.app`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restricted_package_name: Option<String>,
+    /// Data delivered to Android devices instead of `message.data`.
+    ///
+    /// FCM replaces the message data with this map rather than merging the two; use
+    /// [`Message::android_data`](crate::Message::android_data) to only override some keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<HashMap<String, String>>,
+    /// Android-specific notification options.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification: Option<AndroidNotification>,
+    /// When `true`, the message is delivered while the device is in direct boot mode, i.e.
+    /// restarted but not yet unlocked.
+    ///
+    /// The app must handle it in a direct-boot-aware component (`android:directBootAware`),
+    /// which can only access device-protected storage; the crate cannot check this. Messages
+    /// that should wake such a device promptly also need `HIGH` priority.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direct_boot_ok: Option<bool>,
+}
+
+impl AndroidConfig {
+    /// Returns `true` for a high-priority display notification without a channel id.
+    ///
+    /// On Android 8.0 and later the channel's importance decides how a notification is shown, so
+    /// such a notification usually doesn't pop up as its priority suggests.
+    pub(crate) fn lacks_channel_for_priority(&self) -> bool {
+        self.priority == Some(AndroidMessagePriority::High)
+            && self
+                .notification
+                .as_ref()
+                .is_none_or(|notification| notification.channel_id.is_none())
+    }
+
+    /// Collects the mistakes FCM would reject in these options.
+    pub(crate) fn validation_errors(&self, errors: &mut Vec<ValidationError>) {
+        if let Some(package) = &self.restricted_package_name {
+            if !is_valid_package_name(package) {
+                errors.push(ValidationError::new(
+                    "android.restricted_package_name",
+                    format!(
+                        "android.restricted_package_name `{}` is not a valid Android package name",
+                        package
+                    ),
+                ));
+            }
+        }
+        if let Some(light_settings) = self
+            .notification
+            .as_ref()
+            .and_then(|notification| notification.light_settings.as_ref())
+        {
+            if let Err(error) = light_settings.rgba() {
+                errors.push(ValidationError::from_error(
+                    "android.notification.light_settings.color",
+                    error,
+                ));
+            }
+        }
+    }
+}
+
+/// The data key that carries the group of a notification sent with
+/// [`MessageBuilder::android_group`](crate::MessageBuilder::android_group).
+pub const ANDROID_GROUP_DATA_KEY: &str = "android_group";
+
+/// Returns `true` for names like `com.example.app`: at least two dot-separated segments, each
+/// starting with a letter and containing only letters, digits and underscores.
+fn is_valid_package_name(name: &str) -> bool {
+    let mut segments = 0;
+    for segment in name.split('.') {
+        let mut chars = segment.chars();
+        let starts_with_letter = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+        if !starts_with_letter || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return false;
+        }
+        segments += 1;
+    }
+    segments >= 2
+}
+
+/// Notification options that only apply to Android devices.
+///
+/// Serialized as `message.android.notification`; unset fields are left out.
+///
+/// # Example
+/// ```rust
+/// # use fcm_notification::AndroidNotification;
+/// let notification = AndroidNotification {
+///     default_sound: Some(true),
+///     default_vibrate_timings: Some(true),
+///     ..AndroidNotification::default()
+/// };
+/// assert_eq!(
+///     serde_json::to_value(&notification).unwrap(),
+///     serde_json::json!({ "default_sound": true, "default_vibrate_timings": true })
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AndroidNotification {
+    /// The string resource key used to localize the title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_loc_key: Option<String>,
+    /// The format arguments substituted into the localized title.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub title_loc_args: Vec<String>,
+    /// The string resource key used to localize the body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_loc_key: Option<String>,
+    /// The format arguments substituted into the localized body.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub body_loc_args: Vec<String>,
+    /// The sound played when the notification arrives: a raw resource name of the app, or
+    /// `default`. Ignored for channels on Android 8.0 and later, where the channel decides.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    /// The notification channel the notification is posted to on Android 8.0 and later.
+    ///
+    /// The app must have created the channel; otherwise the channel from the app manifest, or
+    /// FCM's default channel, is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    /// Identifies the notification in the tray: a new notification with the same tag replaces
+    /// the existing one instead of being shown next to it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// The `https://` URL of an image shown in the notification, see
+    /// [`Message::image`](crate::Message::image).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Text announced by accessibility services when the notification arrives.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticker: Option<String>,
+    /// When `true`, the notification stays until the app cancels it instead of being dismissed
+    /// when tapped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticky: Option<bool>,
+    /// When `true`, plays the system default sound instead of a custom one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_sound: Option<bool>,
+    /// When `true`, uses the system default vibration pattern.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_vibrate_timings: Option<bool>,
+    /// When `true`, uses the system default notification LED settings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_light_settings: Option<bool>,
+    /// The time shown on the notification, e.g. when a chat message was written, instead of the
+    /// time it was delivered.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_timestamp"
+    )]
+    pub event_time: Option<DateTime<Utc>>,
+    /// The number of items the notification stands for, shown as the launcher icon badge on
+    /// launchers that support it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification_count: Option<i32>,
+    /// Custom notification LED settings, for devices that have one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light_settings: Option<LightSettings>,
+}
+
+/// How the notification LED blinks, serialized as `message.android.notification.light_settings`.
+///
+/// FCM expects the color as separate `red`, `green`, `blue` and `alpha` components between 0 and
+/// 1, which is converted from the usual hex notation here.
+///
+/// # Example
+/// ```rust
+/// # use fcm_notification::LightSettings;
+/// # use std::time::Duration;
+/// let lights = LightSettings::new("#FF0000", Duration::from_millis(500), Duration::from_secs(2));
+/// assert_eq!(
+///     serde_json::to_value(&lights).unwrap(),
+///     serde_json::json!({
+///         "color": { "red": 1.0, "green": 0.0, "blue": 0.0, "alpha": 1.0 },
+///         "light_on_duration": "0.5s",
+///         "light_off_duration": "2s"
+///     })
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightSettings {
+    /// The LED color as `#RRGGBB` or `#RRGGBBAA`; the `#` is optional.
+    pub color: String,
+    /// How long the LED stays on per blink.
+    pub light_on_duration: Duration,
+    /// How long the LED stays off between blinks.
+    pub light_off_duration: Duration,
+}
+
+impl LightSettings {
+    /// Creates LED settings from a hex color and the on/off durations of a blink.
+    pub fn new(color: &str, light_on_duration: Duration, light_off_duration: Duration) -> Self {
+        Self {
+            color: color.to_string(),
+            light_on_duration,
+            light_off_duration,
+        }
+    }
+
+    /// Returns the color as `[red, green, blue, alpha]` components between 0 and 1.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::InvalidArgument`] if the color is not valid hex notation.
+    fn rgba(&self) -> Result<[f32; 4], FcmError> {
+        parse_hex_color(&self.color).ok_or_else(|| {
+            FcmError::InvalidArgument(format!(
+                "android.notification.light_settings.color `{}` is not a #RRGGBB or #RRGGBBAA color",
+                self.color
+            ))
+        })
+    }
+}
+
+impl Serialize for LightSettings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Color {
+            red: f32,
+            green: f32,
+            blue: f32,
+            alpha: f32,
+        }
+
+        #[derive(Serialize)]
+        struct Wire {
+            color: Color,
+            light_on_duration: String,
+            light_off_duration: String,
+        }
+
+        let [red, green, blue, alpha] = self.rgba().map_err(S::Error::custom)?;
+        Wire {
+            color: Color {
+                red,
+                green,
+                blue,
+                alpha,
+            },
+            light_on_duration: crate::duration::format(self.light_on_duration),
+            light_off_duration: crate::duration::format(self.light_off_duration),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Serializes a timestamp in the RFC 3339 `Z` form FCM expects, e.g. `2024-05-01T12:30:00Z`.
+fn serialize_timestamp<S: Serializer>(
+    time: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serializer.serialize_str(&time.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA` into components between 0 and 1; alpha defaults to opaque.
+fn parse_hex_color(color: &str) -> Option<[f32; 4]> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if !matches!(hex.len(), 6 | 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut rgba = [1.0; 4];
+    for (component, index) in rgba.iter_mut().zip((0..hex.len()).step_by(2)) {
+        *component = u8::from_str_radix(&hex[index..index + 2], 16).ok()? as f32 / 255.0;
+    }
+    Some(rgba)
+}
+
+/// The delivery priority of an Android message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AndroidMessagePriority {
+    /// Delivered immediately unless the device is in Doze; may be delayed to save battery.
+    Normal,
+    /// Delivered immediately, waking a sleeping device if necessary.
+    High,
+}
@@ -0,0 +1,162 @@
+//! APNs-specific options for messages delivered to Apple devices.
+
+use crate::ValidationError;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Options applied to messages delivered through the Apple Push Notification service.
+///
+/// Serialized as `message.apns`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ApnsConfig {
+    /// Raw APNs request headers, e.g. `apns-priority` or `apns-push-type`.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// The APNs payload, including the `aps` dictionary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<ApnsPayload>,
+    /// FCM options for APNs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fcm_options: Option<ApnsFcmOptions>,
+}
+
+/// FCM-specific options for messages delivered to Apple devices.
+///
+/// Serialized as `message.apns.fcm_options`.
+///
+/// # Example
+/// ```rust
+/// # use fcm_notification::ApnsFcmOptions;
+/// let options = ApnsFcmOptions {
+///     image: Some("https://example.com/cat.png".to_string()),
+///     ..ApnsFcmOptions::default()
+/// };
+/// assert_eq!(
+///     serde_json::to_value(&options).unwrap(),
+///     serde_json::json!({ "image": "https://example.com/cat.png" })
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ApnsFcmOptions {
+    /// A label used to group iOS messages in FCM delivery reports, overriding
+    /// `message.fcm_options.analytics_label`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analytics_label: Option<String>,
+    /// The `https://` URL of an image shown in the notification.
+    ///
+    /// iOS 10 or later downloads and attaches it in the app's Notification Service Extension,
+    /// using the Firebase Messaging extension helper; it needs `aps.mutable-content = 1` and is
+    /// ignored for background pushes, which display nothing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// The longest `apns-collapse-id` header APNs accepts, in bytes.
+pub const MAX_APNS_COLLAPSE_ID_LEN: usize = 64;
+
+/// The JSON payload sent to APNs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ApnsPayload {
+    /// The Apple-defined `aps` dictionary.
+    pub aps: Aps,
+    /// Custom keys placed next to `aps`.
+    #[serde(flatten)]
+    pub custom: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The Apple-defined `aps` dictionary.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Aps {
+    /// Set to `1` to wake the app in the background.
+    #[serde(rename = "content-available", skip_serializing_if = "Option::is_none")]
+    pub content_available: Option<u8>,
+    /// Set to `1` to let the app's Notification Service Extension modify the notification, e.g.
+    /// to download and attach an image.
+    #[serde(rename = "mutable-content", skip_serializing_if = "Option::is_none")]
+    pub mutable_content: Option<u8>,
+    /// The alert shown to the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<ApsAlert>,
+    /// The name of a sound file in the app bundle, or `default` for the system sound.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    /// The number shown on the app icon; `0` removes the badge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub badge: Option<u32>,
+}
+
+/// Returns `true` for reverse-DNS names like `com.example.app` or `com.example.app.voip`: at
+/// least two dot-separated segments of letters, digits and hyphens.
+fn is_valid_bundle_id(topic: &str) -> bool {
+    let segments: Vec<&str> = topic.split('.').collect();
+    segments.len() >= 2
+        && segments.iter().all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// The `alert` dictionary of an APNs payload.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ApsAlert {
+    /// The key of the localized title string in the app's `Localizable.strings`.
+    #[serde(rename = "title-loc-key", skip_serializing_if = "Option::is_none")]
+    pub title_loc_key: Option<String>,
+    /// The format arguments substituted into the localized title.
+    #[serde(rename = "title-loc-args", skip_serializing_if = "Vec::is_empty")]
+    pub title_loc_args: Vec<String>,
+    /// The key of the localized body string in the app's `Localizable.strings`.
+    #[serde(rename = "loc-key", skip_serializing_if = "Option::is_none")]
+    pub loc_key: Option<String>,
+    /// The format arguments substituted into the localized body.
+    #[serde(rename = "loc-args", skip_serializing_if = "Vec::is_empty")]
+    pub loc_args: Vec<String>,
+}
+
+impl ApnsConfig {
+    /// Collects the mistakes APNs would reject in these options.
+    pub(crate) fn validation_errors(&self, errors: &mut Vec<ValidationError>) {
+        if let Some(topic) = self.headers.get("apns-topic") {
+            if !is_valid_bundle_id(topic) {
+                errors.push(ValidationError::new(
+                    "apns.headers.apns-topic",
+                    format!(
+                        "apns-topic `{}` is not a bundle id like `com.example.app`",
+                        topic
+                    ),
+                ));
+            }
+        }
+        if let Some(image) = self
+            .fcm_options
+            .as_ref()
+            .and_then(|fcm_options| fcm_options.image.as_deref())
+        {
+            if let Err(error) = crate::message::check_https_url("apns.fcm_options.image", image) {
+                errors.push(ValidationError::from_error("apns.fcm_options.image", error));
+            }
+        }
+    }
+
+    /// Configures a background update push.
+    ///
+    /// Apple only delivers a silent background push if all of the following hold:
+    /// * `aps.content-available` is `1`,
+    /// * the `apns-push-type` header is `background`,
+    /// * the `apns-priority` header is `5` (APNs rejects background pushes sent with priority `10`),
+    /// * the payload carries no alert, sound or badge.
+    ///
+    /// Any `aps.sound` and `aps.badge` are therefore removed.
+    pub(crate) fn set_background(&mut self) {
+        self.headers
+            .insert("apns-push-type".to_string(), "background".to_string());
+        self.headers
+            .insert("apns-priority".to_string(), "5".to_string());
+        let aps = &mut self.payload.get_or_insert_with(ApnsPayload::default).aps;
+        aps.content_available = Some(1);
+        aps.sound = None;
+        aps.badge = None;
+    }
+}
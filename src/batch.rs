@@ -0,0 +1,390 @@
+//! Sending many messages at once.
+
+use crate::{
+    FcmError, FcmNotification, Message, MessageResponse, NotificationKind, SendHook, Target,
+};
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, Stream, StreamExt};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+/// The number of messages a batch keeps in flight unless configured otherwise.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 32;
+
+/// The largest number of messages a [`BatchQueue`](crate::BatchQueue) holds before flushing.
+///
+/// This matches the limit of FCM's former batch endpoint and of the Firebase Admin SDKs'
+/// `sendEach`. FCM's v1 API has no batch endpoint, so every message is its own request and
+/// [`FcmNotification::send_batch`] accepts any number of messages without splitting them.
+pub const MAX_BATCH_SIZE: usize = 500;
+
+/// The outcome of one message of a batch, keyed by the message's target.
+pub type BatchResult = (Target, Result<MessageResponse, FcmError>);
+
+/// The outcome of one token of [`FcmNotification::send_to_tokens`].
+pub type TokenResult = (String, Result<MessageResponse, FcmError>);
+
+/// The outcome of one plan of [`FcmNotification::send_plan`]: the plan's index in the input,
+/// its target and the result.
+pub type PlanResult = (usize, Target, Result<MessageResponse, FcmError>);
+
+/// A callback invoked with a device token that FCM reported as permanently invalid.
+pub type InvalidTokenCallback = Arc<dyn Fn(String) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Options for [`FcmNotification::send_batch_with_options`].
+#[derive(Clone, Default)]
+pub struct BatchOptions {
+    /// Have FCM validate the messages without delivering them.
+    pub validate_only: bool,
+    /// Called for every token FCM reports as definitively invalid, see
+    /// [`on_invalid_token`](Self::on_invalid_token).
+    pub on_invalid_token: Option<InvalidTokenCallback>,
+    /// Stop the batch at the first authentication error (see [`FcmError::is_auth_error`]).
+    ///
+    /// Since every other message would fail the same way, the sends still in flight are
+    /// cancelled, the remaining messages are not attempted, and only the outcomes gathered so
+    /// far are returned, including the auth error. Off by default, so that one failure never
+    /// stops a batch.
+    pub fail_fast_on_auth: bool,
+}
+
+impl BatchOptions {
+    /// Registers a callback for tokens that can be deleted from the caller's datastore.
+    ///
+    /// It is awaited once for each message whose `Target::Token` FCM rejected with an error for
+    /// which [`FcmError::is_invalid_token`] holds, i.e. the token is unregistered or malformed.
+    /// Transient failures, quota errors and payload errors never trigger it.
+    pub fn on_invalid_token<F, Fut>(mut self, callback: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_invalid_token = Some(Arc::new(move |token| Box::pin(callback(token))));
+        self
+    }
+}
+
+impl fmt::Debug for BatchOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchOptions")
+            .field("validate_only", &self.validate_only)
+            .field("on_invalid_token", &self.on_invalid_token.is_some())
+            .field("fail_fast_on_auth", &self.fail_fast_on_auth)
+            .finish()
+    }
+}
+
+impl FcmNotification {
+    /// Sends every message and returns the outcomes in the order of `messages`.
+    ///
+    /// Messages are sent individually, with up to the configured batch concurrency in flight
+    /// (see [`with_batch_concurrency`](Self::with_batch_concurrency)), all sharing one cached
+    /// access token. A failed message doesn't stop the rest of the batch.
+    ///
+    /// # Arguments
+    /// * `messages` - The messages to send.
+    pub async fn send_batch(&self, messages: Vec<Message>) -> Vec<BatchResult> {
+        self.send_batch_with_options(messages, &BatchOptions::default())
+            .await
+    }
+
+    /// Sends a copy of `message` to each of `tokens`, replacing its target.
+    ///
+    /// The outcomes are returned in the order of `tokens`, whatever order the sends complete
+    /// in, so they can be matched with the input by index. Sending works like
+    /// [`send_batch`](Self::send_batch).
+    ///
+    /// # Arguments
+    /// * `tokens` - The device registration tokens to send to.
+    /// * `message` - The message to send; its target is ignored.
+    pub async fn send_to_tokens<S: AsRef<str>>(
+        &self,
+        tokens: &[S],
+        message: &Message,
+    ) -> Vec<TokenResult> {
+        let messages = tokens
+            .iter()
+            .map(|token| Message {
+                target: Target::Token(token.as_ref().to_string()),
+                ..message.clone()
+            })
+            .collect();
+        let results = self.send_batch(messages).await;
+        tokens
+            .iter()
+            .zip(results)
+            .map(|(token, (_, result))| (token.as_ref().to_string(), result))
+            .collect()
+    }
+
+    /// Validates every message with FCM without delivering any of them.
+    ///
+    /// Each message goes through FCM's `validate_only` path, concurrently, and the outcomes are
+    /// reported exactly like [`send_batch`](Self::send_batch) would, in the order of `messages`.
+    /// This is a cheap way to catch malformed payloads or dead tokens before a campaign.
+    ///
+    /// # Arguments
+    /// * `messages` - The messages to validate.
+    pub async fn validate_batch(&self, messages: Vec<Message>) -> Vec<BatchResult> {
+        let options = BatchOptions {
+            validate_only: true,
+            ..BatchOptions::default()
+        };
+        self.send_batch_with_options(messages, &options).await
+    }
+
+    /// Sorts `tokens` into those FCM currently accepts and those it reports as invalid, e.g. for
+    /// a periodic cleanup of a token table.
+    ///
+    /// Each token gets a minimal data message through FCM's `validate_only` path, see
+    /// [`validate_batch`](Self::validate_batch), so nothing is delivered to any device. A token
+    /// is only reported as invalid when FCM rejects it with an error for which
+    /// [`FcmError::is_invalid_token`] holds; tokens whose check failed for any other reason,
+    /// e.g. a network error or an exhausted quota, are counted as valid, so they are never
+    /// deleted by mistake. Both lists keep the order of `tokens`.
+    ///
+    /// # Arguments
+    /// * `tokens` - The device registration tokens to check.
+    pub async fn filter_valid_tokens(&self, tokens: &[String]) -> (Vec<String>, Vec<String>) {
+        let messages = tokens
+            .iter()
+            .map(|token| {
+                let data = [("ping".to_string(), "1".to_string())].into();
+                Message::data(Target::Token(token.clone()), data)
+            })
+            .collect();
+
+        let (mut valid, mut invalid) = (Vec::new(), Vec::new());
+        for (target, result) in self.validate_batch(messages).await {
+            let Target::Token(token) = target else {
+                continue;
+            };
+            match result {
+                Err(error) if error.is_invalid_token() => invalid.push(token),
+                _ => valid.push(token),
+            }
+        }
+        (valid, invalid)
+    }
+
+    /// Sends (or validates) every message with the given options, returning the outcomes in
+    /// the order of `messages`.
+    ///
+    /// Any number of messages can be passed. They are all sent through one pipeline with up to
+    /// the configured batch concurrency in flight, so a slow send only holds up its own slot.
+    /// With [`BatchOptions::fail_fast_on_auth`], fewer outcomes than messages may be returned.
+    ///
+    /// # Arguments
+    /// * `messages` - The messages to send.
+    /// * `options` - Options for the whole batch.
+    pub async fn send_batch_with_options(
+        &self,
+        messages: Vec<Message>,
+        options: &BatchOptions,
+    ) -> Vec<BatchResult> {
+        let _batch = BatchScope::start(&self.hooks);
+        let mut outcomes = stream::iter(messages.into_iter().enumerate())
+            .map(|(index, message)| async move {
+                let result = if options.validate_only {
+                    self.validate(&message).await
+                } else {
+                    self.send(&message).await
+                };
+                if let (Some(callback), Target::Token(token), Err(error)) =
+                    (&options.on_invalid_token, &message.target, &result)
+                {
+                    if error.is_invalid_token() {
+                        callback(token.clone()).await;
+                    }
+                }
+                (index, (message.target, result))
+            })
+            .buffer_unordered(self.batch_concurrency);
+
+        let mut results: Vec<(usize, BatchResult)> = Vec::new();
+        while let Some((index, (target, result))) = outcomes.next().await {
+            let aborted =
+                options.fail_fast_on_auth && result.as_ref().is_err_and(FcmError::is_auth_error);
+            results.push((index, (target, result)));
+            if aborted {
+                break;
+            }
+        }
+
+        results.sort_unstable_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Sends every message and yields each outcome as soon as it is available.
+    ///
+    /// Unlike [`send_batch`](Self::send_batch), results arrive in completion order, so callers
+    /// can act on them (e.g. update their database) without waiting for the slowest send or
+    /// holding every result in memory. Messages are only sent while the stream is polled.
+    ///
+    /// # Arguments
+    /// * `messages` - The messages to send.
+    pub fn send_batch_stream<'a, I>(&'a self, messages: I) -> impl Stream<Item = BatchResult> + 'a
+    where
+        I: IntoIterator<Item = Message>,
+        I::IntoIter: 'a,
+    {
+        stream::iter(messages)
+            .map(move |message| async move {
+                let result = self.send(&message).await;
+                (message.target, result)
+            })
+            .buffer_unordered(self.batch_concurrency)
+    }
+
+    /// Sends a mix of plans, each with its own target and content, and yields each outcome as
+    /// soon as it is available.
+    ///
+    /// Tokens, topics and conditions can be mixed freely. Like
+    /// [`send_batch_stream`](Self::send_batch_stream), the sends share one cached access token,
+    /// up to the configured batch concurrency are in flight, results arrive in completion order,
+    /// and nothing is sent while the stream isn't polled. Each item carries the index of its
+    /// plan in `plans`, so it can be correlated with the input.
+    ///
+    /// The data of a plan must be a flat JSON object, see [`to_data_map`](crate::to_data_map);
+    /// other data fails that plan with [`FcmError::InvalidArgument`] without sending it.
+    ///
+    /// # Arguments
+    /// * `plans` - The targets to send to, each with the content to send.
+    pub fn send_plan<'a>(
+        &'a self,
+        plans: Vec<(Target, NotificationKind<'a>)>,
+    ) -> impl Stream<Item = PlanResult> + 'a {
+        stream::iter(plans.into_iter().enumerate())
+            .map(move |(index, (target, kind))| async move {
+                let result = match kind.to_message(target.clone()) {
+                    Ok(message) => self.send(&message).await,
+                    Err(error) => Err(error),
+                };
+                (index, target, result)
+            })
+            .buffer_unordered(self.batch_concurrency)
+    }
+}
+
+/// Tells the hooks about a batch, ending it when dropped so that a cancelled batch ends too.
+struct BatchScope<'a> {
+    hooks: &'a [Arc<dyn SendHook>],
+}
+
+impl<'a> BatchScope<'a> {
+    fn start(hooks: &'a [Arc<dyn SendHook>]) -> Self {
+        for hook in hooks {
+            hook.on_batch_start();
+        }
+        Self { hooks }
+    }
+}
+
+impl Drop for BatchScope<'_> {
+    fn drop(&mut self) {
+        for hook in self.hooks {
+            hook.on_batch_end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockServer, Reply};
+    use crate::{AccessToken, TokenProvider};
+    use std::time::Duration;
+
+    /// A provider that never issues a token, so every send fails before reaching the network.
+    struct Offline;
+
+    impl TokenProvider for Offline {
+        fn fetch_token(&self) -> BoxFuture<'_, Result<AccessToken, FcmError>> {
+            Box::pin(async { Err(FcmError::TokenProviderError("offline".to_string())) })
+        }
+    }
+
+    async fn assert_every_outcome_is_returned(count: usize) {
+        let service = FcmNotification::from_token_provider("test-project", Offline).unwrap();
+        let messages = (0..count)
+            .map(|index| Message::notification(&format!("token-{}", index), "Hello", "World"))
+            .collect();
+
+        let results = service.send_batch(messages).await;
+
+        assert_eq!(results.len(), count);
+        for (index, (target, result)) in results.iter().enumerate() {
+            assert_eq!(target, &Target::Token(format!("token-{}", index)));
+            assert!(matches!(result, Err(FcmError::TokenProviderError(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_of_501_messages_returns_every_outcome() {
+        assert_every_outcome_is_returned(MAX_BATCH_SIZE + 1).await;
+    }
+
+    #[tokio::test]
+    async fn batch_of_1000_messages_returns_every_outcome() {
+        assert_every_outcome_is_returned(2 * MAX_BATCH_SIZE).await;
+    }
+
+    fn tokens(count: usize) -> Vec<String> {
+        (0..count).map(|index| format!("token-{}", index)).collect()
+    }
+
+    #[tokio::test]
+    async fn slow_sends_do_not_hold_up_later_messages() {
+        // The first message is only answered long after the test is over.
+        let server = MockServer::fcm_with(|request| {
+            (request.message()["token"] == "token-0")
+                .then(|| Reply::sent(0).delayed(Duration::from_secs(60)))
+        })
+        .await;
+        let service = server.service();
+
+        let batch = tokio::spawn(async move {
+            service
+                .send_to_tokens(&tokens(1000), &Message::notification("", "Hello", "World"))
+                .await
+        });
+        // Sending in chunks of 500 would hold the second chunk back until the first message is
+        // answered.
+        let every_message_sent = async {
+            while server.sends().len() < 1000 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(30), every_message_sent)
+            .await
+            .expect("later messages waited for the slow one");
+        batch.abort();
+    }
+
+    #[tokio::test]
+    async fn results_keep_the_token_order_whatever_the_latency() {
+        // Later tokens answer sooner, so the sends complete in reverse order.
+        let server = MockServer::fcm_with(|request| {
+            let token = request.message()["token"].as_str().unwrap().to_string();
+            let index: u64 = token.trim_start_matches("token-").parse().unwrap();
+            Some(Reply::sent(index as usize).delayed(Duration::from_millis((8 - index) * 25)))
+        })
+        .await;
+        let service = server.service();
+        let tokens = tokens(8);
+
+        let results = service
+            .send_to_tokens(&tokens, &Message::notification("ignored", "Hello", "World"))
+            .await;
+
+        assert_eq!(results.len(), tokens.len());
+        for (index, (token, result)) in results.iter().enumerate() {
+            assert_eq!(token, &tokens[index]);
+            assert_eq!(
+                result.as_ref().unwrap().name,
+                format!("projects/test-project/messages/{}", index)
+            );
+        }
+    }
+}
@@ -0,0 +1,455 @@
+//! A fluent API for assembling a [`Message`].
+
+use crate::{
+    AndroidConfig, ApnsConfig, FcmError, FcmOptions, LocalizedText, Message, Notification,
+    Platform, Priority, Target, WebpushConfig,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::time::Duration;
+
+/// Builds a [`Message`] step by step and validates it as a whole.
+///
+/// # Example
+/// ```rust
+/// use fcm_notification::{MessageBuilder, Priority, Target};
+/// use std::time::Duration;
+///
+/// let message = MessageBuilder::new(Target::Token("device-token-here".to_string()))
+///     .title("New Like")
+///     .body("Someone liked your post!")
+///     .data("post_id", "42")
+///     .priority(Priority::High)
+///     .ttl(Duration::from_secs(3600))
+///     .analytics_label("likes")
+///     .build()
+///     .unwrap();
+/// assert_eq!(message.data.unwrap()["post_id"], "42");
+/// ```
+///
+/// # Platform options
+/// [`android`](Self::android), [`apns`](Self::apns) and [`webpush`](Self::webpush) replace the
+/// whole config of their platform, including what setters such as
+/// [`android_collapse_key`](Self::android_collapse_key) or [`apns_topic`](Self::apns_topic) put
+/// there. Call them before those setters.
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    message: Message,
+}
+
+impl MessageBuilder {
+    /// Starts a message for the given target.
+    pub fn new(target: Target) -> Self {
+        Self {
+            message: Message::new(target),
+        }
+    }
+
+    /// Sets the notification title.
+    pub fn title(mut self, title: &str) -> Self {
+        self.notification().title = Some(title.to_string());
+        self
+    }
+
+    /// Sets the notification body.
+    pub fn body(mut self, body: &str) -> Self {
+        self.notification().body = Some(body.to_string());
+        self
+    }
+
+    /// Sets a localized title, resolved on the device.
+    pub fn title_loc(mut self, title: LocalizedText) -> Self {
+        self.message.title_loc = Some(title);
+        self
+    }
+
+    /// Sets a localized body, resolved on the device.
+    pub fn body_loc(mut self, body: LocalizedText) -> Self {
+        self.message.body_loc = Some(body);
+        self
+    }
+
+    /// Sets the language tag of the recipient, sent in the message data, see [`Message::locale`].
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.message.locale = Some(locale.to_string());
+        self
+    }
+
+    /// Sets the platform of the target device, leaving out the options of other platforms, see
+    /// [`Message::platform`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use fcm_notification::{MessageBuilder, Platform, Priority, Target};
+    /// let message = MessageBuilder::new(Target::Token("android-device-token".to_string()))
+    ///     .title("Hello")
+    ///     .priority(Priority::High)
+    ///     .platform(Platform::Android)
+    ///     .build()?;
+    ///
+    /// let json = message.to_json()?;
+    /// assert_eq!(json["android"]["priority"], "HIGH");
+    /// assert!(json.get("apns").is_none() && json.get("webpush").is_none());
+    /// # Ok::<(), fcm_notification::FcmError>(())
+    /// ```
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.message.platform = Some(platform);
+        self
+    }
+
+    /// Adds one key/value pair to the message data.
+    pub fn data(mut self, key: &str, value: &str) -> Self {
+        self.message
+            .data
+            .get_or_insert_with(Default::default)
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds every key/value pair of `data` to the message data.
+    pub fn data_map<I, K, V>(mut self, data: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.message
+            .data
+            .get_or_insert_with(Default::default)
+            .extend(data.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Adds a data key/value pair that overrides the message data on Android devices only.
+    pub fn android_data(mut self, key: &str, value: &str) -> Self {
+        self.message
+            .android_data
+            .get_or_insert_with(Default::default)
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds a data key/value pair that overrides the message data on Apple devices only.
+    pub fn apns_data(mut self, key: &str, value: &str) -> Self {
+        self.message
+            .apns_data
+            .get_or_insert_with(Default::default)
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds an extra top-level key to the `message` object, see [`Message::extensions`].
+    pub fn extension(mut self, key: &str, value: Value) -> Self {
+        self.message.extensions.insert(key.to_string(), value);
+        self
+    }
+
+    /// Sets the Android-specific options, replacing any set so far.
+    pub fn android(mut self, android: AndroidConfig) -> Self {
+        self.message.android = Some(android);
+        self
+    }
+
+    /// Sets the APNs-specific options, replacing any set so far.
+    pub fn apns(mut self, apns: ApnsConfig) -> Self {
+        self.message.apns = Some(apns);
+        self
+    }
+
+    /// Sets the WebPush-specific options, replacing any set so far.
+    pub fn webpush(mut self, webpush: WebpushConfig) -> Self {
+        self.message.webpush = Some(webpush);
+        self
+    }
+
+    /// Sets the delivery priority for every platform.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.message.priority = Some(priority);
+        self
+    }
+
+    /// Sets how long the message may wait for an offline device, on every platform.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.message.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the collapse key for every platform, see [`Message::collapse_key`].
+    pub fn collapse_key(mut self, collapse_key: &str) -> Self {
+        self.message.collapse_key = Some(collapse_key.to_string());
+        self
+    }
+
+    /// Sets only the Android collapse key, taking precedence over
+    /// [`collapse_key`](Self::collapse_key).
+    pub fn android_collapse_key(mut self, collapse_key: &str) -> Self {
+        self.message
+            .android
+            .get_or_insert_with(AndroidConfig::default)
+            .collapse_key = Some(collapse_key.to_string());
+        self
+    }
+
+    /// Sends the notification as the latest of a group on Android, e.g. "3 new messages from
+    /// Alice".
+    ///
+    /// FCM has no group field, so the group is conveyed in three parts:
+    /// * `android.notification.tag` is set to `group`, so each notification of the group
+    ///   replaces the previous one in the tray instead of piling up,
+    /// * `android.notification.notification_count` is set to `count`, the number of items
+    ///   the notification stands for, shown as the launcher badge,
+    /// * the [`ANDROID_GROUP_DATA_KEY`](crate::ANDROID_GROUP_DATA_KEY) data key is set to
+    ///   `group` for Android devices only, see [`android_data`](Self::android_data).
+    ///
+    /// The title and body should summarize the group, since they replace the previous
+    /// notification's. When the app handles the message itself, e.g. in the foreground, it can
+    /// read the data key to pass the group to `NotificationCompat.Builder.setGroup` and post a
+    /// summary notification.
+    pub fn android_group(mut self, group: &str, count: i32) -> Self {
+        let notification = self
+            .message
+            .android
+            .get_or_insert_with(AndroidConfig::default)
+            .notification
+            .get_or_insert_with(Default::default);
+        notification.tag = Some(group.to_string());
+        notification.notification_count = Some(count);
+        self.android_data(crate::ANDROID_GROUP_DATA_KEY, group)
+    }
+
+    /// Sets only the APNs `apns-collapse-id` header, taking precedence over
+    /// [`collapse_key`](Self::collapse_key). It may be at most
+    /// [`MAX_APNS_COLLAPSE_ID_LEN`](crate::MAX_APNS_COLLAPSE_ID_LEN) bytes long.
+    pub fn apns_collapse_id(mut self, collapse_id: &str) -> Self {
+        self.message
+            .apns
+            .get_or_insert_with(ApnsConfig::default)
+            .headers
+            .insert("apns-collapse-id".to_string(), collapse_id.to_string());
+        self
+    }
+
+    /// Sets the APNs `apns-topic` header, the bundle id of the app, e.g. `com.example.app`.
+    ///
+    /// FCM normally fills it in from the app registration, so this is only needed to override
+    /// it, e.g. with a `.voip` or `.complication` suffix for those push types.
+    pub fn apns_topic(mut self, bundle_id: &str) -> Self {
+        self.message
+            .apns
+            .get_or_insert_with(ApnsConfig::default)
+            .headers
+            .insert("apns-topic".to_string(), bundle_id.to_string());
+        self
+    }
+
+    /// Sets the WebPush `Topic` header, which makes the push service replace a push that is
+    /// still waiting for the browser with this one if both have the same topic.
+    ///
+    /// This is unrelated to FCM topics. It may be at most
+    /// [`MAX_WEBPUSH_TOPIC_LEN`](crate::MAX_WEBPUSH_TOPIC_LEN) characters of the URL-safe
+    /// base64 alphabet.
+    pub fn webpush_topic(mut self, topic: &str) -> Self {
+        self.message
+            .webpush
+            .get_or_insert_with(WebpushConfig::default)
+            .headers
+            .insert("Topic".to_string(), topic.to_string());
+        self
+    }
+
+    /// Sets an absolute delivery deadline on every platform, see [`Message::expires_at`].
+    pub fn expires_at(mut self, deadline: DateTime<Utc>) -> Self {
+        self.message.expires_at = Some(deadline);
+        self
+    }
+
+    /// Sets the notification sound on every platform, see [`Message::sound`].
+    pub fn sound(mut self, sound: &str) -> Self {
+        self.message.sound = Some(sound.to_string());
+        self
+    }
+
+    /// Sets an image shown on every platform, see [`Message::image`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use fcm_notification::{MessageBuilder, Target};
+    /// let message = MessageBuilder::new(Target::Topic("cats".to_string()))
+    ///     .title("Cat of the day")
+    ///     .image("https://example.com/cat.png")
+    ///     .build()?;
+    ///
+    /// let json = message.to_json()?;
+    /// assert_eq!(json["notification"]["image"], "https://example.com/cat.png");
+    /// assert_eq!(json["android"]["notification"]["image"], "https://example.com/cat.png");
+    /// assert_eq!(json["apns"]["fcm_options"]["image"], "https://example.com/cat.png");
+    ///
+    /// let insecure = MessageBuilder::new(Target::Topic("cats".to_string()))
+    ///     .title("Cat of the day")
+    ///     .image("http://example.com/cat.png")
+    ///     .build();
+    /// assert!(insecure.is_err());
+    /// # Ok::<(), fcm_notification::FcmError>(())
+    /// ```
+    pub fn image(mut self, url: &str) -> Self {
+        self.message.image = Some(url.to_string());
+        self
+    }
+
+    /// Sets the analytics label used in FCM delivery reports.
+    pub fn analytics_label(mut self, label: &str) -> Self {
+        self.message
+            .fcm_options
+            .get_or_insert_with(FcmOptions::default)
+            .analytics_label = Some(label.to_string());
+        self
+    }
+
+    /// Marks the message as an iOS background update, see [`Message::background`].
+    pub fn background(mut self, background: bool) -> Self {
+        self.message.background = background;
+        self
+    }
+
+    /// Validates the message and returns it.
+    ///
+    /// On top of the checks every send runs, this rejects combinations that contradict each
+    /// other: a literal title together with a localized one (and likewise for the body), and a
+    /// background message that also carries a notification.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::InvalidArgument`] describing the first problem found.
+    pub fn build(self) -> Result<Message, FcmError> {
+        let message = self.message;
+        let notification = message.notification.as_ref();
+
+        if message.title_loc.is_some() && notification.is_some_and(|n| n.title.is_some()) {
+            return Err(FcmError::InvalidArgument(
+                "set either a title or a localized title, not both".to_string(),
+            ));
+        }
+        if message.body_loc.is_some() && notification.is_some_and(|n| n.body.is_some()) {
+            return Err(FcmError::InvalidArgument(
+                "set either a body or a localized body, not both".to_string(),
+            ));
+        }
+        if message.background && notification.is_some() {
+            return Err(FcmError::InvalidArgument(
+                "background messages must not carry a notification".to_string(),
+            ));
+        }
+
+        message.check()?;
+        Ok(message)
+    }
+
+    fn notification(&mut self) -> &mut Notification {
+        self.message
+            .notification
+            .get_or_insert_with(Notification::default)
+    }
+}
+
+impl Message {
+    /// Starts building a message for the given target, see [`MessageBuilder`].
+    pub fn builder(target: Target) -> MessageBuilder {
+        MessageBuilder::new(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WebpushFcmOptions;
+    use serde_json::Value;
+
+    fn token() -> Target {
+        Target::Token("device-token".to_string())
+    }
+
+    fn wire(message: &Message) -> Value {
+        serde_json::to_value(message.prepared().as_ref()).unwrap()
+    }
+
+    #[test]
+    fn built_messages_carry_every_setting() {
+        let message = Message::builder(token())
+            .title("Hello")
+            .body("World")
+            .data("chat_id", "42")
+            .priority(Priority::High)
+            .ttl(Duration::from_secs(60))
+            .collapse_key("chat-42")
+            .build()
+            .unwrap();
+
+        let wire = wire(&message);
+        assert_eq!(wire["notification"]["title"], "Hello");
+        assert_eq!(wire["data"]["chat_id"], "42");
+        assert_eq!(wire["android"]["ttl"], "60s");
+        assert_eq!(wire["android"]["priority"], "HIGH");
+        assert_eq!(wire["android"]["collapse_key"], "chat-42");
+        assert_eq!(wire["apns"]["headers"]["apns-collapse-id"], "chat-42");
+    }
+
+    #[test]
+    fn literal_and_localized_titles_conflict() {
+        let result = Message::builder(token())
+            .title("Hello")
+            .title_loc(LocalizedText::new("greeting", ["Alice"]))
+            .build();
+
+        assert!(matches!(result, Err(FcmError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn background_messages_cannot_carry_a_notification() {
+        let result = Message::builder(token())
+            .title("Hello")
+            .background(true)
+            .build();
+
+        assert!(matches!(result, Err(FcmError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn build_runs_the_send_checks() {
+        let result = Message::builder(token())
+            .webpush(WebpushConfig {
+                fcm_options: Some(WebpushFcmOptions {
+                    link: Some("http://example.com/inbox".to_string()),
+                }),
+                ..WebpushConfig::default()
+            })
+            .build();
+
+        assert!(matches!(result, Err(FcmError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn platform_configs_replace_what_setters_put_there() {
+        let android = || AndroidConfig {
+            restricted_package_name: Some("com.example.app".to_string()),
+            ..AndroidConfig::default()
+        };
+
+        let before = Message::builder(token())
+            .android_collapse_key("chat-42")
+            .android(android())
+            .build()
+            .unwrap();
+        let after = Message::builder(token())
+            .android(android())
+            .android_collapse_key("chat-42")
+            .build()
+            .unwrap();
+
+        assert_eq!(before.android.unwrap().collapse_key, None);
+        let android = after.android.unwrap();
+        assert_eq!(android.collapse_key.as_deref(), Some("chat-42"));
+        assert_eq!(
+            android.restricted_package_name.as_deref(),
+            Some("com.example.app")
+        );
+    }
+}
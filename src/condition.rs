@@ -0,0 +1,176 @@
+//! Building FCM topic condition expressions.
+
+use crate::{FcmError, Target};
+
+/// The maximum number of topics FCM allows in one condition.
+pub const MAX_CONDITION_TOPICS: usize = 5;
+
+/// Builds a condition expression for [`Target::Condition`] from topic names.
+///
+/// Topics are combined strictly left to right: each `and` or `or` applies to everything built
+/// so far. Whenever the operator changes, the expression so far is wrapped in parentheses, so
+/// the result doesn't depend on how FCM ranks `&&` against `||`.
+///
+/// # Example
+/// ```rust
+/// # use fcm_notification::{ConditionBuilder, FcmError};
+/// let condition = ConditionBuilder::topic("news").or("sports").and("tech").build()?;
+/// assert_eq!(
+///     condition,
+///     "('news' in topics || 'sports' in topics) && 'tech' in topics"
+/// );
+///
+/// let too_many = ConditionBuilder::topic("a").or("b").or("c").or("d").or("e").or("f").build();
+/// assert!(matches!(too_many, Err(FcmError::InvalidArgument(_))));
+/// # Ok::<(), FcmError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionBuilder {
+    first: String,
+    rest: Vec<(&'static str, String)>,
+}
+
+impl ConditionBuilder {
+    /// Starts a condition matching devices subscribed to `topic`.
+    pub fn topic(topic: &str) -> Self {
+        Self {
+            first: topic.to_string(),
+            rest: Vec::new(),
+        }
+    }
+
+    /// Additionally requires a subscription to `topic`, on top of the condition so far.
+    pub fn and(mut self, topic: &str) -> Self {
+        self.rest.push(("&&", topic.to_string()));
+        self
+    }
+
+    /// Alternatively accepts a subscription to `topic`, instead of the condition so far.
+    pub fn or(mut self, topic: &str) -> Self {
+        self.rest.push(("||", topic.to_string()));
+        self
+    }
+
+    /// Returns the condition expression.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::InvalidArgument`] if the condition uses more than
+    /// [`MAX_CONDITION_TOPICS`] topics or a topic name is not of the form `[a-zA-Z0-9-_.~%]+`.
+    pub fn build(&self) -> Result<String, FcmError> {
+        let topics = 1 + self.rest.len();
+        if topics > MAX_CONDITION_TOPICS {
+            return Err(FcmError::InvalidArgument(format!(
+                "conditions support at most {} topics, got {}",
+                MAX_CONDITION_TOPICS, topics
+            )));
+        }
+
+        let mut condition = in_topics(&self.first)?;
+        let mut previous = None;
+        for (operator, topic) in &self.rest {
+            if previous.is_some_and(|previous| previous != *operator) {
+                condition = format!("({})", condition);
+            }
+            condition.push_str(&format!(" {} {}", operator, in_topics(topic)?));
+            previous = Some(*operator);
+        }
+        Ok(condition)
+    }
+
+    /// Returns the condition as a message target.
+    ///
+    /// # Errors
+    /// Returns an error if the condition is invalid, see [`build`](Self::build).
+    pub fn build_target(&self) -> Result<Target, FcmError> {
+        self.build().map(Target::Condition)
+    }
+}
+
+/// Formats the `'<topic>' in topics` clause for a topic, checking its name.
+fn in_topics(topic: &str) -> Result<String, FcmError> {
+    check_topic_name(topic)?;
+    Ok(format!("'{}' in topics", topic))
+}
+
+/// Checks that `topic` is a valid FCM topic name, without the `/topics/` prefix.
+pub(crate) fn check_topic_name(topic: &str) -> Result<(), FcmError> {
+    let valid_chars = topic
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.~%".contains(c));
+    if topic.is_empty() || !valid_chars {
+        return Err(FcmError::InvalidArgument(format!(
+            "topic `{}` must be one or more characters of [a-zA-Z0-9-_.~%]",
+            topic
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_topic_has_no_operator() {
+        assert_eq!(
+            ConditionBuilder::topic("news").build().unwrap(),
+            "'news' in topics"
+        );
+    }
+
+    #[test]
+    fn repeated_operators_are_not_parenthesized() {
+        let condition = ConditionBuilder::topic("a")
+            .and("b")
+            .and("c")
+            .build()
+            .unwrap();
+        assert_eq!(condition, "'a' in topics && 'b' in topics && 'c' in topics");
+    }
+
+    #[test]
+    fn changing_operators_group_what_came_before() {
+        let condition = ConditionBuilder::topic("a")
+            .or("b")
+            .and("c")
+            .build()
+            .unwrap();
+        assert_eq!(
+            condition,
+            "('a' in topics || 'b' in topics) && 'c' in topics"
+        );
+
+        let condition = ConditionBuilder::topic("a")
+            .and("b")
+            .or("c")
+            .and("d")
+            .build()
+            .unwrap();
+        assert_eq!(
+            condition,
+            "(('a' in topics && 'b' in topics) || 'c' in topics) && 'd' in topics"
+        );
+    }
+
+    #[test]
+    fn invalid_topic_names_are_rejected() {
+        for topic in ["", "news feed", "news'"] {
+            let result = ConditionBuilder::topic("a").or(topic).build();
+            assert!(
+                matches!(result, Err(FcmError::InvalidArgument(_))),
+                "{:?}",
+                topic
+            );
+        }
+    }
+
+    #[test]
+    fn too_many_topics_are_rejected() {
+        let builder = ConditionBuilder::topic("a").or("b").or("c").or("d").or("e");
+        assert!(builder.build().is_ok());
+        assert!(matches!(
+            builder.or("f").build(),
+            Err(FcmError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+//! Helpers for building the FCM `data` map from typed values.
+
+use crate::FcmError;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Converts a serializable value into an FCM `data` map.
+///
+/// FCM only accepts flat string-to-string data, so the value is flattened by these rules:
+/// * it must serialize to a JSON object (a struct or map); each field becomes one key,
+/// * strings are used as-is, numbers and booleans are converted with their JSON text
+///   (`42`, `1.5`, `true`),
+/// * `null` fields, such as `Option::None`, are left out,
+/// * nested objects and arrays are rejected, since they have no string representation FCM
+///   clients agree on.
+///
+/// # Example
+/// ```rust
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct ChatData {
+///     chat_id: u64,
+///     sender: String,
+///     muted: bool,
+/// }
+///
+/// let data = fcm_notification::to_data_map(&ChatData {
+///     chat_id: 42,
+///     sender: "alice".to_string(),
+///     muted: false,
+/// })
+/// .unwrap();
+/// assert_eq!(data["chat_id"], "42");
+/// assert_eq!(data["muted"], "false");
+/// ```
+///
+/// # Errors
+/// Returns [`FcmError::InvalidArgument`] if the value is not an object or contains nested values,
+/// and [`FcmError::JsonParseError`] if it cannot be serialized.
+pub fn to_data_map<T: Serialize + ?Sized>(value: &T) -> Result<HashMap<String, String>, FcmError> {
+    let Value::Object(fields) = serde_json::to_value(value)? else {
+        return Err(FcmError::InvalidArgument(
+            "data must serialize to a JSON object".to_string(),
+        ));
+    };
+
+    let mut data = HashMap::with_capacity(fields.len());
+    for (key, value) in fields {
+        let value = match value {
+            Value::Null => continue,
+            Value::String(value) => value,
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::Array(_) | Value::Object(_) => {
+                return Err(FcmError::InvalidArgument(format!(
+                    "data field `{}` is nested; FCM data values must be strings",
+                    key
+                )))
+            }
+        };
+        data.insert(key, value);
+    }
+
+    Ok(data)
+}
+
+/// A `data` convention of a message type, a schema version and a JSON payload.
+///
+/// It is sent as three flat data keys:
+/// * `type` - the message type, e.g. `chat_message`,
+/// * `version` - the schema version of the payload, as decimal text,
+/// * `payload` - the payload serialized as a JSON string, so it may be nested.
+///
+/// # Example
+/// ```rust
+/// use fcm_notification::{DataEnvelope, Message, Target};
+///
+/// let envelope = DataEnvelope::new("chat_message", 2, &serde_json::json!({ "chat_id": 42 }))?;
+/// let message = Message::data(Target::Topic("chats".to_string()), envelope.to_data());
+///
+/// let data = message.data.unwrap();
+/// assert_eq!(data["type"], "chat_message");
+/// assert_eq!(data["version"], "2");
+/// assert_eq!(data["payload"], r#"{"chat_id":42}"#);
+/// # Ok::<(), fcm_notification::FcmError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataEnvelope {
+    /// The message type, sent as the `type` key.
+    pub kind: String,
+    /// The schema version of the payload, sent as the `version` key.
+    pub version: u32,
+    /// The payload, sent JSON-encoded as the `payload` key.
+    pub payload: Value,
+}
+
+impl DataEnvelope {
+    /// The data key holding the message type.
+    pub const TYPE_KEY: &'static str = "type";
+    /// The data key holding the schema version.
+    pub const VERSION_KEY: &'static str = "version";
+    /// The data key holding the JSON-encoded payload.
+    pub const PAYLOAD_KEY: &'static str = "payload";
+
+    /// Creates an envelope, serializing `payload` to JSON.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::JsonParseError`] if the payload cannot be serialized.
+    pub fn new<T: Serialize + ?Sized>(
+        kind: &str,
+        version: u32,
+        payload: &T,
+    ) -> Result<Self, FcmError> {
+        Ok(Self {
+            kind: kind.to_string(),
+            version,
+            payload: serde_json::to_value(payload)?,
+        })
+    }
+
+    /// Returns the envelope as an FCM `data` map.
+    pub fn to_data(&self) -> HashMap<String, String> {
+        HashMap::from([
+            (Self::TYPE_KEY.to_string(), self.kind.clone()),
+            (Self::VERSION_KEY.to_string(), self.version.to_string()),
+            (Self::PAYLOAD_KEY.to_string(), self.payload.to_string()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn null_fields_are_left_out() {
+        let data = to_data_map(&json!({"sender": "alice", "muted": null, "count": 1.5})).unwrap();
+
+        assert_eq!(
+            data,
+            HashMap::from([
+                ("sender".to_string(), "alice".to_string()),
+                ("count".to_string(), "1.5".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn non_objects_and_nested_values_are_rejected() {
+        for value in [json!("text"), json!([1, 2]), json!({"tags": ["a"]})] {
+            assert!(
+                matches!(to_data_map(&value), Err(FcmError::InvalidArgument(_))),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn envelopes_encode_the_payload_as_json() {
+        let envelope = DataEnvelope::new("chat_message", 3, &json!({"chat": {"id": 42}})).unwrap();
+        let data = envelope.to_data();
+
+        assert_eq!(data[DataEnvelope::TYPE_KEY], "chat_message");
+        assert_eq!(data[DataEnvelope::VERSION_KEY], "3");
+        assert_eq!(
+            serde_json::from_str::<Value>(&data[DataEnvelope::PAYLOAD_KEY]).unwrap(),
+            json!({"chat": {"id": 42}})
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Serialization of durations in the protobuf JSON format used by FCM, e.g. `"3.5s"`.
+
+use serde::Serializer;
+use std::time::Duration;
+
+/// Formats a duration as seconds with an `s` suffix, keeping only significant fractional digits.
+pub(crate) fn format(duration: Duration) -> String {
+    let nanos = duration.subsec_nanos();
+    if nanos == 0 {
+        return format!("{}s", duration.as_secs());
+    }
+    let fraction = format!("{:09}", nanos);
+    format!("{}.{}s", duration.as_secs(), fraction.trim_end_matches('0'))
+}
+
+/// Serializes an optional duration; used with `skip_serializing_if = "Option::is_none"`.
+pub(crate) fn serialize_option<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_str(&format(*duration)),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,199 @@
+//! Readiness probing before large sends.
+
+use crate::{FcmApiError, FcmError, FcmNotification, Message, Target};
+use std::time::Duration;
+
+/// How long [`FcmNotification::probe`] waits for an access token before giving up.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A registration token that is guaranteed to be rejected, used to probe FCM without delivering.
+const HEALTH_CHECK_TOKEN: &str = "fcm-notification-health-check";
+
+/// The result of [`FcmNotification::health_check`].
+#[derive(Debug)]
+pub enum HealthStatus {
+    /// Authentication succeeded and FCM processed the request for the configured project.
+    Healthy,
+    /// The access token could not be obtained, or FCM rejected it (HTTP 401/403).
+    AuthFailed(FcmError),
+    /// FCM does not know the configured project (HTTP 404 without an FCM error code).
+    ProjectNotFound(FcmError),
+    /// The project's sending quota is currently exhausted (HTTP 429).
+    QuotaExceeded(FcmError),
+    /// Any other failure, such as a network error, an FCM outage or a request FCM rejected for
+    /// a reason other than the dummy token.
+    Unavailable(FcmError),
+}
+
+impl HealthStatus {
+    /// Returns `true` if the service is ready to send.
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, HealthStatus::Healthy)
+    }
+}
+
+/// The result of [`FcmNotification::probe`].
+#[derive(Debug)]
+pub enum ProbeResult {
+    /// An access token was obtained.
+    Ok,
+    /// The credentials are broken, e.g. a revoked key or a malformed service account file.
+    ///
+    /// Retrying won't help, so this is the case to alert on rather than to restart for.
+    CredentialError(FcmError),
+    /// The token endpoint was unreachable, too slow or temporarily failing.
+    ///
+    /// This is usually transient. Errors that fit neither class are reported here as well, so
+    /// that an unknown failure never makes a probe restart the process.
+    NetworkError(FcmError),
+}
+
+impl ProbeResult {
+    /// Returns `true` if an access token was obtained.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, ProbeResult::Ok)
+    }
+
+    /// Classifies an error of obtaining an access token.
+    fn from_error(error: FcmError) -> Self {
+        match error.root_cause() {
+            #[cfg(not(target_arch = "wasm32"))]
+            FcmError::JwtEncodeError(_) => ProbeResult::CredentialError(error),
+            FcmError::FileReadError(_)
+            | FcmError::JsonParseError(_)
+            | FcmError::InvalidPrivateKey(_)
+            | FcmError::NotAServiceAccount { .. } => ProbeResult::CredentialError(error),
+            // `invalid_grant` and friends; 429 and 5xx are the endpoint's own trouble.
+            FcmError::TokenRequestError { status, .. }
+                if (400..500).contains(status) && *status != 429 =>
+            {
+                ProbeResult::CredentialError(error)
+            }
+            _ => ProbeResult::NetworkError(error),
+        }
+    }
+}
+
+impl FcmNotification {
+    /// Checks that an access token can be obtained, for liveness and readiness probes.
+    ///
+    /// Unlike [`health_check`](Self::health_check), this doesn't contact FCM itself, and it tells
+    /// broken credentials apart from network trouble so a probe can avoid restart loops over a
+    /// network blip. A still-valid cached token counts as success without a request. The token
+    /// fetch is abandoned after [`PROBE_TIMEOUT`], which is reported as a network error.
+    pub async fn probe(&self) -> ProbeResult {
+        match crate::timer::timeout(PROBE_TIMEOUT, self.get_access_token()).await {
+            Some(Ok(_)) => ProbeResult::Ok,
+            Some(Err(error)) => ProbeResult::from_error(error),
+            None => ProbeResult::NetworkError(FcmError::Timeout(PROBE_TIMEOUT)),
+        }
+    }
+
+    /// Checks that the credentials and project are usable, without delivering anything.
+    ///
+    /// This performs a `validate_only` send to a dummy registration token. FCM rejecting that
+    /// token is the expected outcome: it means the access token was accepted and the project
+    /// exists, so it is reported as [`HealthStatus::Healthy`]. Any other HTTP 400 means FCM
+    /// refused the request itself and is reported as [`HealthStatus::Unavailable`].
+    pub async fn health_check(&self) -> HealthStatus {
+        let mut message = Message::new(Target::Token(HEALTH_CHECK_TOKEN.to_string()));
+        message.data = Some([("health_check".to_string(), "1".to_string())].into());
+
+        let error = match self.validate(&message).await {
+            Ok(_) => return HealthStatus::Healthy,
+            Err(error) => error,
+        };
+
+        if rejects_dummy_token(&error) {
+            return HealthStatus::Healthy;
+        }
+        match error.status() {
+            Some(401 | 403) => HealthStatus::AuthFailed(error),
+            Some(404) => HealthStatus::ProjectNotFound(error),
+            Some(429) => HealthStatus::QuotaExceeded(error),
+            Some(_) => HealthStatus::Unavailable(error),
+            None => match error.root_cause() {
+                #[cfg(not(target_arch = "wasm32"))]
+                FcmError::JwtEncodeError(_) => HealthStatus::AuthFailed(error),
+                FcmError::InvalidPrivateKey(_)
+                | FcmError::AccessTokenNotFound
+                | FcmError::TokenRequestError { .. }
+                | FcmError::TokenProviderError(_) => HealthStatus::AuthFailed(error),
+                _ => HealthStatus::Unavailable(error),
+            },
+        }
+    }
+}
+
+/// Returns `true` if FCM rejected the dummy registration token itself.
+///
+/// That is an `UNREGISTERED` error or an `INVALID_ARGUMENT` naming `message.token`, either by
+/// its FCM error code or by a field violation.
+fn rejects_dummy_token(error: &FcmError) -> bool {
+    let names_token = |api_error: &FcmApiError| {
+        api_error.error_status.as_deref() == Some("INVALID_ARGUMENT")
+            && api_error
+                .details
+                .iter()
+                .flat_map(|detail| &detail.field_violations)
+                .any(|violation| violation.field == "message.token")
+    };
+    error.is_invalid_token() || error.api_error().is_some_and(names_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockServer, Reply};
+    use serde_json::json;
+
+    /// An `INVALID_ARGUMENT` error response whose field violation names `field`.
+    fn invalid_argument(field: &str) -> Reply {
+        Reply::json(
+            400,
+            json!({"error": {
+                "code": 400,
+                "message": "Request contains an invalid argument.",
+                "status": "INVALID_ARGUMENT",
+                "details": [{
+                    "@type": "type.googleapis.com/google.rpc.BadRequest",
+                    "fieldViolations": [{"field": field, "description": "Invalid value"}],
+                }],
+            }}),
+        )
+    }
+
+    #[tokio::test]
+    async fn rejected_dummy_token_is_healthy() {
+        let server = MockServer::fcm_with(|_| Some(invalid_argument("message.token"))).await;
+
+        assert!(server.service().health_check().await.is_healthy());
+        assert_eq!(server.sends()[0].json()["validate_only"], true);
+    }
+
+    #[tokio::test]
+    async fn other_invalid_arguments_are_unavailable() {
+        let server = MockServer::fcm_with(|_| Some(invalid_argument("message.data"))).await;
+
+        let status = server.service().health_check().await;
+        assert!(matches!(status, HealthStatus::Unavailable(_)));
+    }
+
+    #[tokio::test]
+    async fn unregistered_dummy_token_is_healthy() {
+        let server = MockServer::fcm_with(|_| {
+            Some(Reply::fcm_error(404, "NOT_FOUND", Some("UNREGISTERED")))
+        })
+        .await;
+
+        assert!(server.service().health_check().await.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn unknown_project_is_reported() {
+        let server = MockServer::fcm_with(|_| Some(Reply::fcm_error(404, "NOT_FOUND", None))).await;
+
+        let status = server.service().health_check().await;
+        assert!(matches!(status, HealthStatus::ProjectNotFound(_)));
+    }
+}
@@ -0,0 +1,316 @@
+//! Observing sends, including the built-in [`log`]-based logging.
+
+use crate::{FcmError, MessageResponse, Notification, Target};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Describes a send, as passed to [`SendHook`]s.
+#[derive(Debug, Clone, Copy)]
+pub struct SendEvent<'a> {
+    /// The recipient of the message.
+    pub target: &'a Target,
+    /// The notification of the message, if it has one.
+    pub notification: Option<&'a Notification>,
+    /// The project the message was sent for.
+    pub project_id: &'a str,
+    /// Whether the message was only validated.
+    pub validate_only: bool,
+}
+
+/// Callbacks invoked with the final outcome of every send, after any retries.
+///
+/// Hooks run inline on the sending task, so they should return quickly.
+pub trait SendHook: Send + Sync {
+    /// Called when FCM accepted a message.
+    fn on_success(&self, event: &SendEvent<'_>, response: &MessageResponse) {
+        let _ = (event, response);
+    }
+
+    /// Called when a send failed.
+    fn on_error(&self, event: &SendEvent<'_>, error: &FcmError) {
+        let _ = (event, error);
+    }
+
+    /// Called before the messages of a batch are sent, see
+    /// [`FcmNotification::send_batch`](crate::FcmNotification::send_batch).
+    fn on_batch_start(&self) {}
+
+    /// Called once every message of a batch has been reported.
+    fn on_batch_end(&self) {}
+}
+
+/// A [`SendHook`] that reports sends through the [`log`] crate.
+///
+/// Successes are logged at `debug` level and failures at `warn` level, under the
+/// `fcm_notification` target.
+///
+/// Device tokens and notification content may be personal data. With `redact` enabled, tokens
+/// are shortened to their first [`LogHook::TOKEN_PREFIX_LEN`] characters and the notification
+/// title and body are left out entirely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogHook {
+    /// Whether to redact tokens and omit notification content.
+    pub redact: bool,
+}
+
+impl LogHook {
+    /// The number of token characters kept when redacting.
+    pub const TOKEN_PREFIX_LEN: usize = 8;
+
+    /// Creates a hook that logs full tokens and notification content.
+    pub fn new() -> Self {
+        Self { redact: false }
+    }
+
+    /// Creates a hook that redacts tokens and omits notification content.
+    pub fn redacted() -> Self {
+        Self { redact: true }
+    }
+
+    /// Formats the target and, unless redacting, the notification content of an event.
+    pub fn describe(&self, event: &SendEvent<'_>) -> String {
+        let mut description = match event.target {
+            Target::Token(token) if self.redact => format!("token {}", redact_token(token)),
+            Target::Token(token) => format!("token {}", token),
+            Target::Topic(topic) => format!("topic {}", topic),
+            Target::Condition(condition) => format!("condition {}", condition),
+        };
+        if let (false, Some(notification)) = (self.redact, event.notification) {
+            description.push_str(&format!(
+                " (title: {:?}, body: {:?})",
+                notification.title.as_deref().unwrap_or_default(),
+                notification.body.as_deref().unwrap_or_default()
+            ));
+        }
+        description
+    }
+}
+
+impl Default for LogHook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SendHook for LogHook {
+    fn on_success(&self, event: &SendEvent<'_>, response: &MessageResponse) {
+        log::debug!(
+            target: "fcm_notification",
+            "{} message to {} in project {} in {:?} (cached token: {}): {}",
+            if event.validate_only { "validated" } else { "sent" },
+            self.describe(event),
+            event.project_id,
+            response.latency,
+            response.token_from_cache,
+            response.name
+        );
+    }
+
+    fn on_error(&self, event: &SendEvent<'_>, error: &FcmError) {
+        log::warn!(
+            target: "fcm_notification",
+            "failed to send message to {} in project {}: {}",
+            self.describe(event),
+            event.project_id,
+            error
+        );
+    }
+}
+
+/// A [`LogHook`] that limits how many failures it logs per batch.
+///
+/// When a large batch fails wholesale, e.g. during an authentication outage, logging every
+/// failure would flood the logs. This hook logs the first `max_errors` failures of each batch
+/// and then a single warning with the number of failures it left out. Sends outside of batches
+/// are always logged.
+///
+/// The limit applies per hook, so batches running concurrently on one service share it.
+#[derive(Debug)]
+pub struct ThrottledLogHook {
+    inner: LogHook,
+    max_errors: usize,
+    batches: AtomicUsize,
+    logged: AtomicUsize,
+    suppressed: AtomicUsize,
+}
+
+impl ThrottledLogHook {
+    /// Wraps `inner`, logging at most `max_errors` failures per batch.
+    pub fn new(inner: LogHook, max_errors: usize) -> Self {
+        Self {
+            inner,
+            max_errors,
+            batches: AtomicUsize::new(0),
+            logged: AtomicUsize::new(0),
+            suppressed: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl SendHook for ThrottledLogHook {
+    fn on_success(&self, event: &SendEvent<'_>, response: &MessageResponse) {
+        self.inner.on_success(event, response);
+    }
+
+    fn on_error(&self, event: &SendEvent<'_>, error: &FcmError) {
+        if self.batches.load(Ordering::Relaxed) > 0
+            && self.logged.fetch_add(1, Ordering::Relaxed) >= self.max_errors
+        {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.inner.on_error(event, error);
+    }
+
+    fn on_batch_start(&self) {
+        if self.batches.fetch_add(1, Ordering::Relaxed) == 0 {
+            self.logged.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn on_batch_end(&self) {
+        if self.batches.fetch_sub(1, Ordering::Relaxed) != 1 {
+            return;
+        }
+        let suppressed = self.suppressed.swap(0, Ordering::Relaxed);
+        if suppressed > 0 {
+            log::warn!(
+                target: "fcm_notification",
+                "{} more messages of the batch failed to send; their errors were not logged",
+                suppressed
+            );
+        }
+        self.logged.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Shortens a device token to a prefix that is enough to correlate log lines.
+pub(crate) fn redact_token(token: &str) -> String {
+    let prefix: String = token.chars().take(LogHook::TOKEN_PREFIX_LEN).collect();
+    format!("{}…", prefix)
+}
+
+/// Logs a warning if a send is dropped before it finished, see
+/// [`FcmNotification::with_cancellation_logging`](crate::FcmNotification::with_cancellation_logging).
+pub(crate) struct CancellationGuard {
+    description: String,
+    project_id: String,
+    finished: bool,
+}
+
+impl CancellationGuard {
+    pub(crate) fn new(event: &SendEvent<'_>) -> Self {
+        Self {
+            description: LogHook::redacted().describe(event),
+            project_id: event.project_id.to_string(),
+            finished: false,
+        }
+    }
+
+    /// Marks the send as finished, so dropping the guard logs nothing.
+    pub(crate) fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for CancellationGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            log::warn!(
+                target: "fcm_notification",
+                "send of message to {} in project {} was cancelled; it may or may not have been delivered",
+                self.description,
+                self.project_id
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::Message;
+    use std::sync::Arc;
+
+    fn event<'a>(target: &'a Target, notification: Option<&'a Notification>) -> SendEvent<'a> {
+        SendEvent {
+            target,
+            notification,
+            project_id: "test-project",
+            validate_only: false,
+        }
+    }
+
+    #[test]
+    fn redacted_descriptions_leave_out_personal_data() {
+        let target = Target::Token("abcdefghijklmnop".to_string());
+        let notification = Notification {
+            title: Some("Hello".to_string()),
+            body: Some("World".to_string()),
+            image: None,
+        };
+        let event = event(&target, Some(&notification));
+
+        assert_eq!(
+            LogHook::new().describe(&event),
+            r#"token abcdefghijklmnop (title: "Hello", body: "World")"#
+        );
+        assert_eq!(LogHook::redacted().describe(&event), "token abcdefgh…");
+    }
+
+    #[test]
+    fn throttled_hook_suppresses_errors_beyond_the_limit() {
+        let hook = ThrottledLogHook::new(LogHook::redacted(), 2);
+        let target = Target::Topic("news".to_string());
+        let error = FcmError::AccessTokenNotFound;
+
+        hook.on_batch_start();
+        for _ in 0..5 {
+            hook.on_error(&event(&target, None), &error);
+        }
+        assert_eq!(hook.suppressed.load(Ordering::Relaxed), 3);
+        hook.on_batch_end();
+        assert_eq!(hook.suppressed.load(Ordering::Relaxed), 0);
+
+        // Outside of batches every error is logged.
+        hook.on_error(&event(&target, None), &error);
+        assert_eq!(hook.suppressed.load(Ordering::Relaxed), 0);
+    }
+
+    #[derive(Default)]
+    struct BatchCounter {
+        starts: AtomicUsize,
+        ends: AtomicUsize,
+        sends: AtomicUsize,
+    }
+
+    impl SendHook for Arc<BatchCounter> {
+        fn on_success(&self, _: &SendEvent<'_>, _: &MessageResponse) {
+            self.sends.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_batch_start(&self) {
+            self.starts.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_batch_end(&self) {
+            self.ends.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_are_reported_once_around_their_sends() {
+        let server = MockServer::fcm().await;
+        let counter = Arc::new(BatchCounter::default());
+        let service = server.service().with_hook(counter.clone());
+
+        let messages = (0..3)
+            .map(|index| Message::notification(&format!("token-{}", index), "Hello", "World"))
+            .collect();
+        service.send_batch(messages).await;
+
+        assert_eq!(counter.starts.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.ends.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.sends.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,1314 @@
+//! Owned message types mirroring the FCM HTTP v1 `Message` resource.
+//!
+//! Unlike [`NotificationPayload`](crate::NotificationPayload), these types own their data, so they
+//! can be queued, moved across tasks and sent from background workers.
+
+use crate::{
+    AndroidConfig, AndroidMessagePriority, AndroidNotification, ApnsConfig, ApnsFcmOptions,
+    ApnsPayload, Aps, ApsAlert, FcmError, WebpushConfig, MAX_APNS_COLLAPSE_ID_LEN,
+    MAX_MESSAGE_SIZE, MAX_WEBPUSH_ACTIONS,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// The recipient of an FCM message.
+///
+/// Serialized into the `message` object as exactly one of `token`, `topic` or `condition`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// A device registration token.
+    Token(String),
+    /// A topic name, without the `/topics/` prefix.
+    ///
+    /// FCM reports one result for the whole topic rather than one per subscribed device. A
+    /// topic FCM reports as missing fails with [`FcmError::TopicNotFound`].
+    Topic(String),
+    /// A condition expression, e.g. `'news' in topics && 'sports' in topics`.
+    Condition(String),
+}
+
+/// The user-visible part of a message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    /// The title of the notification.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The body of the notification.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// The `https://` URL of an image shown in the notification.
+    ///
+    /// Prefer [`Message::image`], which also wires the image up for Android and iOS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// A localized string, resolved on the device from the app's string resources.
+///
+/// The same value is used for Android (`*_loc_key` / `*_loc_args`) and APNs (`*loc-key` /
+/// `*loc-args`); the arguments are substituted into the localized format string in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalizedText {
+    /// The key of the string resource.
+    pub key: String,
+    /// The format arguments.
+    pub args: Vec<String>,
+}
+
+impl LocalizedText {
+    /// Creates a localized text from a resource key and its format arguments.
+    pub fn new<I, S>(key: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            key: key.to_string(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// The delivery priority of a message, applied consistently to every platform.
+///
+/// FCM has no single priority knob: Android, APNs and WebPush each express it differently, and
+/// setting only one of them is a common mistake. A `Priority` on a [`Message`] is translated into
+/// all three representations:
+///
+/// | Priority | `android.priority` | APNs `apns-priority` | WebPush `Urgency` |
+/// |----------|--------------------|----------------------|-------------------|
+/// | `High`   | `HIGH`             | `10`                 | `high`            |
+/// | `Normal` | `NORMAL`           | `5`                  | `normal`          |
+///
+/// Values set explicitly in the platform configs take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Deliver immediately, waking the device if necessary.
+    High,
+    /// Deliver when convenient for the device's power state.
+    Normal,
+}
+
+impl Priority {
+    fn android(self) -> AndroidMessagePriority {
+        match self {
+            Priority::High => AndroidMessagePriority::High,
+            Priority::Normal => AndroidMessagePriority::Normal,
+        }
+    }
+
+    fn apns(self) -> &'static str {
+        match self {
+            Priority::High => "10",
+            Priority::Normal => "5",
+        }
+    }
+
+    fn webpush_urgency(self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+        }
+    }
+}
+
+/// The kind of device a message is meant for, see [`Message::platform`].
+///
+/// FCM registration tokens don't reveal their platform, so this is a hint the caller keeps
+/// alongside the token, e.g. as reported by the app when it registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Android devices, configured through `message.android`.
+    Android,
+    /// Apple devices, configured through `message.apns`.
+    Ios,
+    /// Browsers, configured through `message.webpush`.
+    Web,
+}
+
+impl Platform {
+    /// Parses a platform name as commonly stored with tokens, ignoring case.
+    ///
+    /// Accepts `android`; `ios`, `apns`, `apple`, `iphone`, `ipad` and `macos`; and `web`,
+    /// `webpush` and `browser`. Anything else, including an empty hint, gives `None`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use fcm_notification::Platform;
+    /// assert_eq!(Platform::from_hint("iOS"), Some(Platform::Ios));
+    /// assert_eq!(Platform::from_hint("webpush"), Some(Platform::Web));
+    /// assert_eq!(Platform::from_hint("unknown"), None);
+    /// ```
+    pub fn from_hint(hint: &str) -> Option<Self> {
+        match hint.trim().to_ascii_lowercase().as_str() {
+            "android" => Some(Platform::Android),
+            "ios" | "apns" | "apple" | "iphone" | "ipad" | "macos" => Some(Platform::Ios),
+            "web" | "webpush" | "browser" => Some(Platform::Web),
+            _ => None,
+        }
+    }
+}
+
+/// Platform-independent FCM options.
+///
+/// Serialized as `message.fcm_options`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FcmOptions {
+    /// A label used to group messages in FCM delivery reports (at most 50 characters of
+    /// `[a-zA-Z0-9-_.~%]`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analytics_label: Option<String>,
+}
+
+/// An FCM message, as placed in the `message` field of a send request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    /// The recipient of the message.
+    #[serde(flatten)]
+    pub target: Target,
+    /// The notification to display, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification: Option<Notification>,
+    /// Optional key/value data delivered to the app.
+    ///
+    /// The keys `from`, `notification`, `message_type` and any key starting with `google` or
+    /// `gcm` are reserved by FCM; messages using them fail validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<HashMap<String, String>>,
+    /// Data keys that differ on Android, merged on top of [`data`](Self::data).
+    ///
+    /// A key present in both maps takes the value from this one on Android devices, while other
+    /// platforms keep the base value. The merged map is sent as `android.data`, unless that is
+    /// set explicitly.
+    #[serde(skip)]
+    pub android_data: Option<HashMap<String, String>>,
+    /// Data keys that differ on Apple devices, merged on top of [`data`](Self::data).
+    ///
+    /// APNs has no data map of its own: these keys are sent as custom keys of the APNs payload,
+    /// next to `aps`, which is where iOS apps read message data from. A key set explicitly in
+    /// `apns.payload` takes precedence.
+    #[serde(skip)]
+    pub apns_data: Option<HashMap<String, String>>,
+    /// Android-specific options.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub android: Option<AndroidConfig>,
+    /// APNs-specific options.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apns: Option<ApnsConfig>,
+    /// WebPush-specific options.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webpush: Option<WebpushConfig>,
+    /// Platform-independent FCM options.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fcm_options: Option<FcmOptions>,
+    /// Extra top-level keys of the `message` object, for FCM fields this crate doesn't model.
+    ///
+    /// The keys are sent next to the modeled fields as-is. They never replace anything the
+    /// crate sends: a key naming a modeled field, such as `token` or `android`, fails
+    /// validation instead.
+    #[serde(flatten, skip_serializing_if = "HashMap::is_empty")]
+    pub extensions: HashMap<String, Value>,
+    /// The delivery priority, applied to Android, APNs and WebPush alike.
+    ///
+    /// A [`background`](Self::background) message always uses APNs priority 5, as Apple requires.
+    #[serde(skip)]
+    pub priority: Option<Priority>,
+    /// How long the message may wait for an offline device, applied to every platform.
+    ///
+    /// This sets `android.ttl`, the APNs `apns-expiration` header (computed from the send time;
+    /// `0` for a zero TTL, which APNs treats as "deliver once or drop") and the WebPush `TTL`
+    /// header. Values set explicitly in the platform configs take precedence. FCM keeps messages
+    /// for at most [`MAX_TTL`], so a longer TTL is rejected.
+    #[serde(skip)]
+    pub ttl: Option<Duration>,
+    /// Identifies a group of messages of which a device only keeps the latest, on every platform.
+    ///
+    /// This sets `android.collapse_key` and the APNs `apns-collapse-id` header, unless they are
+    /// set explicitly. APNs limits the id to [`MAX_APNS_COLLAPSE_ID_LEN`] bytes.
+    #[serde(skip)]
+    pub collapse_key: Option<String>,
+    /// An absolute deadline after which the message is no longer delivered.
+    ///
+    /// This is an alternative to [`ttl`](Self::ttl) for callers that already track expiry times.
+    /// It is converted at send time into `android.ttl` (the time left until the deadline), the
+    /// APNs `apns-expiration` header (the deadline in epoch seconds) and the WebPush `TTL` header.
+    /// A deadline in the past is rejected; setting both `ttl` and `expires_at` is too.
+    #[serde(skip)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Marks a data-only message as an iOS background update.
+    ///
+    /// When set, the message is sent with `apns.payload.aps.content-available = 1`, the
+    /// `apns-push-type: background` header and the `apns-priority: 5` header. Apple silently drops
+    /// background pushes that miss any of the three, and also expects them to carry no alert, so
+    /// leave `notification` unset.
+    #[serde(skip)]
+    pub background: bool,
+    /// The sound played for the notification on Android and iOS, e.g. `default`.
+    ///
+    /// It is only sent with messages that display something. Data-only and
+    /// [`background`](Self::background) messages never carry a sound, and for background
+    /// messages an explicit `aps.sound` or `aps.badge` is removed too: iOS drops background
+    /// pushes that contain them.
+    #[serde(skip)]
+    pub sound: Option<String>,
+    /// The `https://` URL of an image shown in the notification on every platform.
+    ///
+    /// This sets `notification.image` (if the message has a notification) and
+    /// `android.notification.image`. For APNs it sets `apns.fcm_options.image` and
+    /// `aps.mutable-content = 1`, because iOS never downloads images itself: the app needs a
+    /// Notification Service Extension (iOS 10 or later) that hands the notification to the
+    /// Firebase Messaging extension helper, which downloads the image and attaches it before
+    /// the notification is displayed. Without the extension iOS shows the notification without
+    /// the image. The platform fields are left out of data-only and
+    /// [background](Self::background) messages, which display nothing. The URL must use
+    /// `https://`; anything else fails validation.
+    #[serde(skip)]
+    pub image: Option<String>,
+    /// The BCP-47 language tag of the user the message is for, e.g. `de-CH`, so the app can
+    /// pick translations.
+    ///
+    /// It is sent in the message data, under the `locale` key unless the service configures
+    /// another one with [`with_locale_key`](crate::FcmNotification::with_locale_key). A value
+    /// already present under that key in [`data`](Self::data) is kept.
+    #[serde(skip)]
+    pub locale: Option<String>,
+    /// The platform of the target device, if known.
+    ///
+    /// When set, the options of the other platforms are left out of the message at send time,
+    /// e.g. `apns` and `webpush` for an Android token. This keeps shared templates small and
+    /// avoids sending options the device can't use. Leave it unset for topics and conditions,
+    /// which usually reach several platforms.
+    #[serde(skip)]
+    pub platform: Option<Platform>,
+    /// A localized title, sent to Android and APNs in their respective localization fields.
+    #[serde(skip)]
+    pub title_loc: Option<LocalizedText>,
+    /// A localized body, sent to Android and APNs in their respective localization fields.
+    #[serde(skip)]
+    pub body_loc: Option<LocalizedText>,
+}
+
+impl Message {
+    /// Creates an empty message for the given target.
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            notification: None,
+            data: None,
+            android_data: None,
+            apns_data: None,
+            android: None,
+            apns: None,
+            webpush: None,
+            fcm_options: None,
+            extensions: HashMap::new(),
+            priority: None,
+            ttl: None,
+            collapse_key: None,
+            expires_at: None,
+            background: false,
+            sound: None,
+            image: None,
+            locale: None,
+            platform: None,
+            title_loc: None,
+            body_loc: None,
+        }
+    }
+
+    /// Creates a data-only message, which is delivered to the app without displaying anything.
+    pub fn data(target: Target, data: HashMap<String, String>) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::new(target)
+        }
+    }
+
+    /// Creates a display notification addressed to a single device token.
+    pub fn notification(token: &str, title: &str, body: &str) -> Self {
+        Self {
+            notification: Some(Notification {
+                title: Some(title.to_string()),
+                body: Some(body.to_string()),
+                image: None,
+            }),
+            ..Self::new(Target::Token(token.to_string()))
+        }
+    }
+
+    /// Returns the message as it is put on the wire, with convenience fields expanded.
+    pub(crate) fn prepared(&self) -> Cow<'_, Message> {
+        Self::into_prepared(Cow::Borrowed(self))
+    }
+
+    /// Like [`prepared`](Self::prepared), but expands an owned message in place instead of
+    /// cloning it again.
+    pub(crate) fn into_prepared(message: Cow<'_, Message>) -> Cow<'_, Message> {
+        if !message.has_deferred_fields() {
+            return message;
+        }
+        let mut message = message.into_owned();
+        message.expand_deferred_fields();
+        Cow::Owned(message)
+    }
+
+    /// Expands the convenience fields into the platform options, leaving the non-`Copy` ones
+    /// empty since they are not sent themselves.
+    fn expand_deferred_fields(&mut self) {
+        let collapse_key = self.collapse_key.take();
+        let sound = self.sound.take();
+        let image = self.image.take();
+        let android_data = self.android_data.take();
+        let apns_data = self.apns_data.take();
+        let title_loc = self.title_loc.take();
+        let body_loc = self.body_loc.take();
+        if let Some(priority) = self.priority {
+            self.android
+                .get_or_insert_with(AndroidConfig::default)
+                .priority
+                .get_or_insert(priority.android());
+            self.apns
+                .get_or_insert_with(ApnsConfig::default)
+                .headers
+                .entry("apns-priority".to_string())
+                .or_insert_with(|| priority.apns().to_string());
+            self.webpush
+                .get_or_insert_with(WebpushConfig::default)
+                .headers
+                .entry("Urgency".to_string())
+                .or_insert_with(|| priority.webpush_urgency().to_string());
+        }
+        if let Some(ttl) = self.ttl {
+            self.android
+                .get_or_insert_with(AndroidConfig::default)
+                .ttl
+                .get_or_insert(ttl);
+            let expiration = if ttl.is_zero() {
+                0
+            } else {
+                // A TTL this large fails validation; saturate while merely expanding it.
+                i64::try_from(ttl.as_secs())
+                    .ok()
+                    .and_then(|secs| Utc::now().timestamp().checked_add(secs))
+                    .unwrap_or(i64::MAX)
+            };
+            self.apns
+                .get_or_insert_with(ApnsConfig::default)
+                .headers
+                .entry("apns-expiration".to_string())
+                .or_insert_with(|| expiration.to_string());
+            self.webpush
+                .get_or_insert_with(WebpushConfig::default)
+                .headers
+                .entry("TTL".to_string())
+                .or_insert_with(|| ttl.as_secs().to_string());
+        }
+        if let Some(collapse_key) = collapse_key {
+            self.android
+                .get_or_insert_with(AndroidConfig::default)
+                .collapse_key
+                .get_or_insert_with(|| collapse_key.clone());
+            self.apns
+                .get_or_insert_with(ApnsConfig::default)
+                .headers
+                .entry("apns-collapse-id".to_string())
+                .or_insert(collapse_key);
+        }
+        if let Some(deadline) = self.expires_at {
+            let remaining = (deadline - Utc::now()).to_std().unwrap_or_default();
+            self.android
+                .get_or_insert_with(AndroidConfig::default)
+                .ttl
+                .get_or_insert(remaining);
+            self.apns
+                .get_or_insert_with(ApnsConfig::default)
+                .headers
+                .entry("apns-expiration".to_string())
+                .or_insert_with(|| deadline.timestamp().to_string());
+            self.webpush
+                .get_or_insert_with(WebpushConfig::default)
+                .headers
+                .entry("TTL".to_string())
+                .or_insert_with(|| remaining.as_secs().to_string());
+        }
+        let is_alert = self.notification.is_some() || title_loc.is_some() || body_loc.is_some();
+        if let Some(sound) = sound.filter(|_| is_alert && !self.background) {
+            self.android_notification_mut()
+                .sound
+                .get_or_insert_with(|| sound.clone());
+            self.aps_mut().sound.get_or_insert(sound);
+        }
+        if self.background {
+            self.apns
+                .get_or_insert_with(ApnsConfig::default)
+                .set_background();
+        }
+        if let Some(image) = &image {
+            if let Some(notification) = &mut self.notification {
+                notification.image.get_or_insert_with(|| image.clone());
+            }
+            if is_alert && !self.background {
+                self.android_notification_mut()
+                    .image
+                    .get_or_insert_with(|| image.clone());
+                self.aps_mut().mutable_content = Some(1);
+                self.apns
+                    .get_or_insert_with(ApnsConfig::default)
+                    .fcm_options
+                    .get_or_insert_with(ApnsFcmOptions::default)
+                    .image
+                    .get_or_insert_with(|| image.clone());
+            }
+        }
+        if let Some(overrides) = android_data {
+            let base = &self.data;
+            self.android
+                .get_or_insert_with(AndroidConfig::default)
+                .data
+                .get_or_insert_with(|| {
+                    let mut data = base.clone().unwrap_or_default();
+                    data.extend(overrides);
+                    data
+                });
+        }
+        if let Some(overrides) = apns_data {
+            let custom = &mut self
+                .apns
+                .get_or_insert_with(ApnsConfig::default)
+                .payload
+                .get_or_insert_with(ApnsPayload::default)
+                .custom;
+            for (key, value) in overrides {
+                custom.entry(key).or_insert_with(|| value.into());
+            }
+        }
+        if let Some(title) = title_loc {
+            let notification = self.android_notification_mut();
+            notification.title_loc_key = Some(title.key.clone());
+            notification.title_loc_args = title.args.clone();
+            let alert = self.aps_alert_mut();
+            alert.title_loc_key = Some(title.key);
+            alert.title_loc_args = title.args;
+        }
+        if let Some(body) = body_loc {
+            let notification = self.android_notification_mut();
+            notification.body_loc_key = Some(body.key.clone());
+            notification.body_loc_args = body.args.clone();
+            let alert = self.aps_alert_mut();
+            alert.loc_key = Some(body.key);
+            alert.loc_args = body.args;
+        }
+        // Pruned last, so that nothing above recreates the options of another platform.
+        if let Some(platform) = self.platform {
+            if platform != Platform::Android {
+                self.android = None;
+            }
+            if platform != Platform::Ios {
+                self.apns = None;
+            }
+            if platform != Platform::Web {
+                self.webpush = None;
+            }
+        }
+    }
+
+    /// Returns the `message` object as FCM will receive it, with convenience fields such as
+    /// [`image`](Self::image) or [`ttl`](Self::ttl) expanded into the platform options.
+    ///
+    /// Service-wide defaults, such as
+    /// [`with_default_ttl`](crate::FcmNotification::with_default_ttl), are not applied, and the
+    /// message is not validated.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::JsonParseError`] if the message cannot be serialized.
+    pub fn to_json(&self) -> Result<Value, FcmError> {
+        Ok(serde_json::to_value(self.prepared().as_ref())?)
+    }
+
+    /// Checks the message for every mistake that can be detected without contacting FCM.
+    ///
+    /// This runs the same checks as every send, plus the size limit, and reports all problems
+    /// at once instead of stopping at the first one, e.g. to show them next to the fields of a
+    /// form.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use fcm_notification::{FcmOptions, Message, Target};
+    /// let mut message = Message::new(Target::Token(String::new()));
+    /// message.image = Some("http://example.com/cat.png".to_string());
+    /// message.fcm_options = Some(FcmOptions {
+    ///     analytics_label: Some("spring sale!".to_string()),
+    /// });
+    ///
+    /// let errors = message.validate().unwrap_err();
+    /// let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+    /// assert_eq!(fields, ["token", "fcm_options.analytics_label", "image"]);
+    ///
+    /// let empty = Message::notification("device-token", "", "");
+    /// assert_eq!(
+    ///     empty.validate().unwrap_err()[0].message,
+    ///     "notification requires title or body"
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    /// Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = self.validation_errors();
+        match crate::serialized_size(self.prepared().as_ref()) {
+            Ok(size) if size > MAX_MESSAGE_SIZE => errors.push(ValidationError::from_error(
+                "message",
+                FcmError::PayloadTooLarge {
+                    size,
+                    limit: MAX_MESSAGE_SIZE,
+                },
+            )),
+            Ok(_) => {}
+            Err(error) => errors.push(ValidationError::from_error("message", error)),
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks the message for mistakes FCM would reject, before it is sent.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::InvalidArgument`] describing the first problem found.
+    pub(crate) fn check(&self) -> Result<(), FcmError> {
+        match self.validation_errors().into_iter().next() {
+            Some(error) => Err(FcmError::InvalidArgument(error.message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Collects the mistakes FCM would reject, in field order.
+    fn validation_errors(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let (field, value) = match &self.target {
+            Target::Token(token) => ("token", token),
+            Target::Topic(topic) => ("topic", topic),
+            Target::Condition(condition) => ("condition", condition),
+        };
+        if value.trim().is_empty() {
+            errors.push(ValidationError::new(
+                field,
+                format!("the message {} must not be empty", field),
+            ));
+        }
+        for (field, data) in [
+            ("data", self.data.as_ref()),
+            ("android_data", self.android_data.as_ref()),
+            ("apns_data", self.apns_data.as_ref()),
+        ] {
+            let Some(data) = data else { continue };
+            let mut reserved: Vec<&str> = data
+                .keys()
+                .map(String::as_str)
+                .filter(|key| is_reserved_data_key(key))
+                .collect();
+            if !reserved.is_empty() {
+                reserved.sort_unstable();
+                errors.push(ValidationError::new(
+                    field,
+                    format!(
+                        "{} keys {} are reserved by FCM",
+                        field,
+                        reserved
+                            .iter()
+                            .map(|key| format!("`{}`", key))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                ));
+            }
+        }
+        let mut collisions: Vec<&str> = self
+            .extensions
+            .keys()
+            .map(String::as_str)
+            .filter(|key| MESSAGE_FIELDS.contains(key))
+            .collect();
+        if !collisions.is_empty() {
+            collisions.sort_unstable();
+            errors.push(ValidationError::new(
+                "extensions",
+                format!(
+                    "extension keys {} collide with fields the crate sets",
+                    collisions
+                        .iter()
+                        .map(|key| format!("`{}`", key))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ));
+        }
+        if let Some(notification) = &self.notification {
+            let is_blank = |text: &Option<String>| text.as_deref().is_none_or(str::is_empty);
+            if is_blank(&notification.title)
+                && is_blank(&notification.body)
+                && self.title_loc.is_none()
+                && self.body_loc.is_none()
+            {
+                errors.push(ValidationError::new(
+                    "notification",
+                    "notification requires title or body".to_string(),
+                ));
+            }
+        }
+        if let Some(label) = self
+            .fcm_options
+            .as_ref()
+            .and_then(|fcm_options| fcm_options.analytics_label.as_deref())
+        {
+            if let Err(error) = check_analytics_label(label) {
+                errors.push(ValidationError::from_error(
+                    "fcm_options.analytics_label",
+                    error,
+                ));
+            }
+        }
+        if let Some(ttl) = self.ttl.filter(|ttl| *ttl > MAX_TTL) {
+            errors.push(ValidationError::new(
+                "ttl",
+                format!(
+                    "message ttl of {}s exceeds FCM's maximum of {}s (28 days)",
+                    ttl.as_secs(),
+                    MAX_TTL.as_secs()
+                ),
+            ));
+        }
+        if let Some(deadline) = self.expires_at {
+            if self.ttl.is_some() {
+                errors.push(ValidationError::new(
+                    "expires_at",
+                    "set either a ttl or an expiry deadline, not both".to_string(),
+                ));
+            }
+            if deadline <= Utc::now() {
+                errors.push(ValidationError::new(
+                    "expires_at",
+                    format!(
+                        "message expiry deadline {} is in the past",
+                        deadline.to_rfc3339()
+                    ),
+                ));
+            }
+        }
+        let apns_collapse_id = self
+            .apns
+            .as_ref()
+            .and_then(|apns| apns.headers.get("apns-collapse-id"));
+        for (field, collapse_id) in [
+            ("collapse_key", self.collapse_key.as_ref()),
+            ("apns.headers.apns-collapse-id", apns_collapse_id),
+        ] {
+            if let Some(collapse_id) = collapse_id.filter(|id| id.len() > MAX_APNS_COLLAPSE_ID_LEN)
+            {
+                errors.push(ValidationError::new(
+                    field,
+                    format!(
+                        "APNs collapse ids are limited to {} bytes, `{}` has {}",
+                        MAX_APNS_COLLAPSE_ID_LEN,
+                        collapse_id,
+                        collapse_id.len()
+                    ),
+                ));
+            }
+        }
+        if let Some(locale) = &self.locale {
+            if !is_well_formed_language_tag(locale) {
+                errors.push(ValidationError::new(
+                    "locale",
+                    format!(
+                        "locale `{}` is not a well-formed BCP-47 language tag",
+                        locale
+                    ),
+                ));
+            }
+        }
+        if let Some(image) = &self.image {
+            if let Err(error) = check_https_url("image", image) {
+                errors.push(ValidationError::from_error("image", error));
+            }
+        }
+        if let Some(android) = &self.android {
+            android.validation_errors(&mut errors);
+        }
+        if let Some(apns) = &self.apns {
+            apns.validation_errors(&mut errors);
+        }
+        if let Some(webpush) = &self.webpush {
+            webpush.validation_errors(&mut errors);
+        }
+        if let Some(link) = self
+            .webpush
+            .as_ref()
+            .and_then(|webpush| webpush.fcm_options.as_ref())
+            .and_then(|fcm_options| fcm_options.link.as_deref())
+        {
+            if let Err(error) = check_https_url("webpush.fcm_options.link", link) {
+                errors.push(ValidationError::from_error(
+                    "webpush.fcm_options.link",
+                    error,
+                ));
+            }
+        }
+        if let Some(notification) = self.webpush.as_ref().and_then(|w| w.notification.as_ref()) {
+            if notification.actions.len() > MAX_WEBPUSH_ACTIONS {
+                errors.push(ValidationError::new(
+                    "webpush.notification.actions",
+                    format!(
+                        "webpush notifications support at most {} actions, got {}",
+                        MAX_WEBPUSH_ACTIONS,
+                        notification.actions.len()
+                    ),
+                ));
+            }
+        }
+        errors
+    }
+
+    /// Returns `true` if the message asks iOS for a background wake and an alert at once.
+    pub(crate) fn is_silent_alert(&self) -> bool {
+        let aps = self
+            .apns
+            .as_ref()
+            .and_then(|apns| apns.payload.as_ref())
+            .map(|payload| &payload.aps);
+        let wakes = self.background || aps.is_some_and(|aps| aps.content_available == Some(1));
+        let alerts = self.notification.is_some()
+            || self.title_loc.is_some()
+            || self.body_loc.is_some()
+            || aps.is_some_and(|aps| aps.alert.is_some());
+        self.platform
+            .is_none_or(|platform| platform == Platform::Ios)
+            && wakes
+            && alerts
+    }
+
+    /// Splits a [silent alert](Self::is_silent_alert) into a plain alert and a background
+    /// update carrying the same data.
+    pub(crate) fn split_silent_alert(&self) -> (Message, Message) {
+        let mut alert = self.clone();
+        alert.background = false;
+        if let Some(apns) = &mut alert.apns {
+            if apns.headers.get("apns-push-type").map(String::as_str) == Some("background") {
+                apns.headers.remove("apns-push-type");
+            }
+            if let Some(payload) = &mut apns.payload {
+                payload.aps.content_available = None;
+            }
+        }
+
+        let mut silent = self.clone();
+        silent.background = true;
+        silent.notification = None;
+        silent.title_loc = None;
+        silent.body_loc = None;
+        silent.sound = None;
+        silent.image = None;
+        if let Some(payload) = silent.apns.as_mut().and_then(|apns| apns.payload.as_mut()) {
+            payload.aps.alert = None;
+            payload.aps.mutable_content = None;
+        }
+        if let Some(android) = &mut silent.android {
+            android.notification = None;
+        }
+        (alert, silent)
+    }
+
+    /// Returns `true` if any field that is expanded at send time is set.
+    fn has_deferred_fields(&self) -> bool {
+        self.background
+            || self.priority.is_some()
+            || self.ttl.is_some()
+            || self.image.is_some()
+            || self.sound.is_some()
+            || self.expires_at.is_some()
+            || self.collapse_key.is_some()
+            || self.android_data.is_some()
+            || self.apns_data.is_some()
+            || self.platform.is_some()
+            || self.title_loc.is_some()
+            || self.body_loc.is_some()
+    }
+
+    /// Returns `android.notification`, creating it and its parents if needed.
+    pub(crate) fn android_notification_mut(&mut self) -> &mut AndroidNotification {
+        self.android
+            .get_or_insert_with(AndroidConfig::default)
+            .notification
+            .get_or_insert_with(AndroidNotification::default)
+    }
+
+    /// Returns `apns.payload.aps`, creating it and its parents if needed.
+    pub(crate) fn aps_mut(&mut self) -> &mut Aps {
+        &mut self
+            .apns
+            .get_or_insert_with(ApnsConfig::default)
+            .payload
+            .get_or_insert_with(ApnsPayload::default)
+            .aps
+    }
+
+    /// Returns `apns.payload.aps.alert`, creating it and its parents if needed.
+    pub(crate) fn aps_alert_mut(&mut self) -> &mut ApsAlert {
+        self.aps_mut().alert.get_or_insert_with(ApsAlert::default)
+    }
+}
+
+/// A problem found by [`Message::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The path of the offending field within the FCM message, e.g. `webpush.fcm_options.link`.
+    pub field: String,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl ValidationError {
+    pub(crate) fn new(field: &str, message: String) -> Self {
+        Self {
+            field: field.to_string(),
+            message,
+        }
+    }
+
+    /// Converts the error of one of the single-value checks, keeping just its description.
+    pub(crate) fn from_error(field: &str, error: FcmError) -> Self {
+        let message = match error {
+            FcmError::InvalidArgument(message) => message,
+            error => error.to_string(),
+        };
+        Self::new(field, message)
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// The top-level keys of the `message` object this crate may send.
+const MESSAGE_FIELDS: [&str; 9] = [
+    "token",
+    "topic",
+    "condition",
+    "notification",
+    "data",
+    "android",
+    "apns",
+    "webpush",
+    "fcm_options",
+];
+
+/// Data keys FCM reserves for itself, in addition to the `google` and `gcm` prefixes.
+const RESERVED_DATA_KEYS: [&str; 3] = ["from", "notification", "message_type"];
+
+/// Returns `true` for data keys FCM rejects or silently drops, e.g. `from` or `gcm.foo`.
+fn is_reserved_data_key(key: &str) -> bool {
+    RESERVED_DATA_KEYS.contains(&key) || key.starts_with("google") || key.starts_with("gcm")
+}
+
+/// Checks the BCP-47 syntax of a language tag such as `en`, `pt-BR` or `zh-Hant-TW`: a
+/// language subtag of 2 to 8 letters, followed by subtags of 1 to 8 letters or digits.
+///
+/// This doesn't check the subtags against the IANA registry.
+fn is_well_formed_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+    (2..=8).contains(&language.len())
+        && language.bytes().all(|b| b.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+        })
+}
+
+/// The longest time FCM keeps a message for an offline device: 28 days.
+pub const MAX_TTL: Duration = Duration::from_secs(28 * 24 * 60 * 60);
+
+/// The key suffixes that mark a response field as an identifier, e.g. `google.message_id`,
+/// `apns_id` or `messageId`.
+const ID_KEY_SUFFIXES: [&str; 3] = ["_id", ".message_id", "Id"];
+
+/// The maximum length of an analytics label.
+const MAX_ANALYTICS_LABEL_LEN: usize = 50;
+
+/// Checks an analytics label against FCM's `^[a-zA-Z0-9-_.~%]{1,50}$` format.
+fn check_analytics_label(label: &str) -> Result<(), FcmError> {
+    let valid_chars = label
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.~%".contains(c));
+    if label.is_empty() || label.len() > MAX_ANALYTICS_LABEL_LEN || !valid_chars {
+        return Err(FcmError::InvalidArgument(format!(
+            "analytics label `{}` must be 1 to {} characters of [a-zA-Z0-9-_.~%]",
+            label, MAX_ANALYTICS_LABEL_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that `url` is a well-formed `https://` URL, as FCM requires for links and images.
+///
+/// # Errors
+/// Returns [`FcmError::InvalidArgument`] naming `field` if the URL is malformed or not HTTPS.
+pub(crate) fn check_https_url(field: &str, url: &str) -> Result<(), FcmError> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "https" && parsed.has_host() => Ok(()),
+        Ok(_) => Err(FcmError::InvalidArgument(format!(
+            "{} must be an https URL, got `{}`",
+            field, url
+        ))),
+        Err(error) => Err(FcmError::InvalidArgument(format!(
+            "{} is not a valid URL (`{}`): {}",
+            field, url, error
+        ))),
+    }
+}
+
+/// The response returned by FCM for a successfully accepted message.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MessageResponse {
+    /// The identifier of the message, in the form `projects/<project>/messages/<message-id>`.
+    ///
+    /// Empty if a successful response unexpectedly lacks it, rather than failing the send.
+    #[serde(default)]
+    pub name: String,
+    /// The round-trip time of the FCM request that delivered the message.
+    ///
+    /// This only covers the successful HTTP request to FCM, including reading its response. It
+    /// excludes fetching the access token and any earlier failed attempts.
+    #[serde(skip)]
+    pub latency: Duration,
+    /// How long obtaining the access token and the FCM request took, for the successful attempt.
+    #[serde(skip)]
+    pub timings: Timings,
+    /// Whether the access token used for the request was already cached.
+    ///
+    /// This reflects the cache at the time of this particular send: `false` means this send
+    /// fetched a new token itself. A send that waited for another send's token fetch reports
+    /// `true`.
+    #[serde(skip)]
+    pub token_from_cache: bool,
+    /// The complete response body, including fields this crate doesn't model yet.
+    #[serde(skip)]
+    pub raw: Value,
+}
+
+/// Where the time of a send went, see [`MessageResponse::timings`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// The time spent obtaining the access token, including waiting for another send's fetch.
+    ///
+    /// Close to zero when the token came from the cache, so a large value on many sends
+    /// points at the token endpoint rather than at FCM.
+    pub token: Duration,
+    /// The round-trip time of the FCM request, the same as [`MessageResponse::latency`].
+    pub send: Duration,
+}
+
+impl MessageResponse {
+    /// Returns the `<message-id>` part of [`name`](Self::name).
+    ///
+    /// Returns `None` if the name is not of the form `projects/<project>/messages/<message-id>`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let response: fcm_notification::MessageResponse =
+    /// #     serde_json::from_str(r#"{"name": "projects/my-app/messages/0:1500415314455276%31bd1c9631bd1c96"}"#).unwrap();
+    /// assert_eq!(response.message_id(), Some("0:1500415314455276%31bd1c9631bd1c96"));
+    /// ```
+    pub fn message_id(&self) -> Option<&str> {
+        match self.name.split('/').collect::<Vec<_>>().as_slice() {
+            ["projects", project, "messages", id] if !project.is_empty() && !id.is_empty() => {
+                Some(id)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the identifiers FCM returned besides [`name`](Self::name), such as
+    /// `google.message_id`, for correlating the message with other systems.
+    ///
+    /// These are the top-level fields of [`raw`](Self::raw) whose key ends in `_id`,
+    /// `.message_id` or a camelCase `Id`, and whose value is a string or a number. Keys that
+    /// merely end in the letters, such as `android` or `uuid`, don't count. Anything else is
+    /// ignored, so an unexpected response shape yields no ids rather than an error. Numbers are
+    /// returned as decimal text.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let mut response: fcm_notification::MessageResponse =
+    /// #     serde_json::from_str(r#"{"name": "projects/my-app/messages/1"}"#).unwrap();
+    /// response.raw = serde_json::json!({
+    ///     "name": "projects/my-app/messages/1",
+    ///     "google.message_id": "0:1500415314455276%31bd1c96",
+    ///     "details": { "id": "nested ids are ignored" },
+    /// });
+    /// assert_eq!(
+    ///     response.secondary_ids(),
+    ///     vec![("google.message_id", "0:1500415314455276%31bd1c96".to_string())]
+    /// );
+    /// ```
+    pub fn secondary_ids(&self) -> Vec<(&str, String)> {
+        let Some(fields) = self.raw.as_object() else {
+            return Vec::new();
+        };
+        fields
+            .iter()
+            .filter(|(key, _)| ID_KEY_SUFFIXES.iter().any(|suffix| key.ends_with(suffix)))
+            .filter_map(|(key, value)| match value {
+                Value::String(id) => Some((key.as_str(), id.clone())),
+                Value::Number(id) => Some((key.as_str(), id.to_string())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WebpushFcmOptions;
+    use serde_json::{json, Value};
+
+    /// Returns the `message` object as it would be sent, without service defaults.
+    fn wire(message: &Message) -> Value {
+        serde_json::to_value(message.prepared().as_ref()).unwrap()
+    }
+
+    #[test]
+    fn high_priority_is_set_for_every_platform() {
+        let mut message = Message::notification("device-token", "Hello", "World");
+        message.priority = Some(Priority::High);
+
+        let wire = wire(&message);
+        assert_eq!(wire["android"]["priority"], "HIGH");
+        assert_eq!(wire["apns"]["headers"]["apns-priority"], "10");
+        assert_eq!(wire["webpush"]["headers"]["Urgency"], "high");
+        assert!(wire.get("priority").is_none());
+    }
+
+    #[test]
+    fn explicit_platform_priorities_are_kept() {
+        let mut message = Message::notification("device-token", "Hello", "World");
+        message.priority = Some(Priority::High);
+        message.apns = Some(ApnsConfig {
+            headers: [("apns-priority".to_string(), "5".to_string())].into(),
+            ..ApnsConfig::default()
+        });
+
+        let wire = wire(&message);
+        assert_eq!(wire["android"]["priority"], "HIGH");
+        assert_eq!(wire["apns"]["headers"]["apns-priority"], json!("5"));
+    }
+
+    #[test]
+    fn ttls_beyond_28_days_are_rejected() {
+        let with_ttl = |ttl: Duration| {
+            let mut message = Message::notification("device-token", "Hello", "World");
+            message.ttl = Some(ttl);
+            message
+        };
+
+        assert!(with_ttl(MAX_TTL).validation_errors().is_empty());
+        let errors = with_ttl(MAX_TTL + Duration::from_secs(1)).validation_errors();
+        assert_eq!(errors[0].field, "ttl");
+
+        // Expanding a TTL too large for a timestamp must not overflow.
+        let huge = with_ttl(Duration::from_secs(u64::MAX));
+        assert_eq!(huge.validation_errors()[0].field, "ttl");
+        assert_eq!(
+            wire(&huge)["apns"]["headers"]["apns-expiration"],
+            i64::MAX.to_string()
+        );
+    }
+
+    #[test]
+    fn webpush_links_must_be_https() {
+        let with_link = |link: &str| {
+            let mut message = Message::notification("device-token", "Hello", "World");
+            message.webpush = Some(WebpushConfig {
+                fcm_options: Some(WebpushFcmOptions {
+                    link: Some(link.to_string()),
+                }),
+                ..WebpushConfig::default()
+            });
+            message.validation_errors()
+        };
+
+        assert!(with_link("https://example.com/inbox").is_empty());
+
+        let http = with_link("http://example.com/inbox");
+        assert_eq!(http[0].field, "webpush.fcm_options.link");
+        assert!(http[0].message.contains("must be an https URL"));
+
+        let malformed = with_link("example.com/inbox");
+        assert_eq!(malformed[0].field, "webpush.fcm_options.link");
+        assert!(malformed[0].message.contains("is not a valid URL"));
+    }
+
+    #[test]
+    fn background_sends_carry_no_sound() {
+        let mut message = Message::new(Target::Token("device-token".to_string()));
+        message.data = Some([("sync".to_string(), "1".to_string())].into());
+        message.background = true;
+        message.sound = Some("chime.caf".to_string());
+        message.aps_mut().sound = Some("explicit.caf".to_string());
+        message.aps_mut().badge = Some(3);
+
+        let wire = wire(&message);
+        let aps = &wire["apns"]["payload"]["aps"];
+        assert_eq!(aps["content-available"], 1);
+        assert!(aps.get("sound").is_none());
+        assert!(aps.get("badge").is_none());
+        assert_eq!(wire["apns"]["headers"]["apns-push-type"], "background");
+        assert!(wire.get("android").is_none());
+    }
+
+    #[test]
+    fn alerts_carry_the_sound() {
+        let mut message = Message::notification("device-token", "Hello", "World");
+        message.sound = Some("chime.caf".to_string());
+
+        let wire = wire(&message);
+        assert_eq!(wire["apns"]["payload"]["aps"]["sound"], "chime.caf");
+        assert_eq!(wire["android"]["notification"]["sound"], "chime.caf");
+    }
+
+    #[test]
+    fn platform_data_merges_over_the_base_data() {
+        let mut message = Message::new(Target::Token("device-token".to_string()));
+        message.data = Some(
+            [
+                ("screen".to_string(), "inbox".to_string()),
+                ("chat_id".to_string(), "42".to_string()),
+            ]
+            .into(),
+        );
+        message.android_data = Some([("screen".to_string(), "InboxActivity".to_string())].into());
+        message.apns_data =
+            Some([("screen".to_string(), "InboxViewController".to_string())].into());
+
+        let wire = wire(&message);
+        assert_eq!(wire["data"], json!({"screen": "inbox", "chat_id": "42"}));
+        assert_eq!(
+            wire["android"]["data"],
+            json!({"screen": "InboxActivity", "chat_id": "42"})
+        );
+        let payload = &wire["apns"]["payload"];
+        assert_eq!(payload["screen"], "InboxViewController");
+        assert!(payload.get("chat_id").is_none());
+    }
+
+    #[test]
+    fn explicit_platform_data_takes_precedence() {
+        let mut message = Message::notification("device-token", "Hello", "World");
+        message.data = Some([("screen".to_string(), "inbox".to_string())].into());
+        message.android_data = Some([("screen".to_string(), "InboxActivity".to_string())].into());
+        message.apns_data =
+            Some([("screen".to_string(), "InboxViewController".to_string())].into());
+        message.android = Some(AndroidConfig {
+            data: Some([("screen".to_string(), "explicit".to_string())].into()),
+            ..AndroidConfig::default()
+        });
+        message
+            .apns
+            .get_or_insert_with(ApnsConfig::default)
+            .payload
+            .get_or_insert_with(ApnsPayload::default)
+            .custom
+            .insert("screen".to_string(), json!("explicit"));
+
+        let wire = wire(&message);
+        assert_eq!(wire["android"]["data"], json!({"screen": "explicit"}));
+        assert_eq!(wire["apns"]["payload"]["screen"], "explicit");
+        assert_eq!(wire["data"], json!({"screen": "inbox"}));
+    }
+
+    #[test]
+    fn extensions_are_merged_into_the_message() {
+        let mut message = Message::notification("device-token", "Hello", "World");
+        message
+            .extensions
+            .insert("new_field".to_string(), json!({"enabled": true}));
+
+        let wire = wire(&message);
+        assert_eq!(wire["new_field"], json!({"enabled": true}));
+        assert_eq!(wire["token"], "device-token");
+        assert_eq!(wire["notification"]["title"], "Hello");
+        assert!(message.validation_errors().is_empty());
+    }
+
+    #[test]
+    fn extensions_naming_modeled_fields_are_rejected() {
+        let mut message = Message::notification("device-token", "Hello", "World");
+        message
+            .extensions
+            .insert("token".to_string(), json!("other-token"));
+        message.extensions.insert("android".to_string(), json!({}));
+
+        let errors = message.validation_errors();
+        assert_eq!(errors[0].field, "extensions");
+        assert_eq!(
+            errors[0].message,
+            "extension keys `android`, `token` collide with fields the crate sets"
+        );
+        assert!(matches!(message.check(), Err(FcmError::InvalidArgument(_))));
+    }
+
+    #[tokio::test]
+    async fn colliding_extensions_are_not_sent() {
+        let server = crate::mock::MockServer::fcm().await;
+        let mut message = Message::notification("device-token", "Hello", "World");
+        message
+            .extensions
+            .insert("topic".to_string(), json!("news"));
+
+        let error = server.service().send(&message).await.unwrap_err();
+
+        assert!(matches!(error, FcmError::InvalidArgument(_)));
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn secondary_ids_need_an_id_suffix() {
+        let mut response: MessageResponse =
+            serde_json::from_str(r#"{"name": "projects/my-app/messages/1"}"#).unwrap();
+        response.raw = json!({
+            "name": "projects/my-app/messages/1",
+            "google.message_id": "0:1500415314455276%31bd1c96",
+            "apns_id": 7,
+            "deliveryId": "d-1",
+            "android": "not an id",
+            "valid": "not an id",
+            "uuid": "not an id",
+        });
+
+        let mut ids = response.secondary_ids();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                ("apns_id", "7".to_string()),
+                ("deliveryId", "d-1".to_string()),
+                (
+                    "google.message_id",
+                    "0:1500415314455276%31bd1c96".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! A minimal HTTP server standing in for Google's token endpoint and FCM in unit tests.
+
+use crate::{FcmNotification, ServiceAccount};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    /// The headers, with lowercase names.
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: String,
+}
+
+impl Request {
+    /// Parses the body as JSON.
+    pub(crate) fn json(&self) -> Value {
+        serde_json::from_str(&self.body).expect("request body is JSON")
+    }
+
+    /// Returns the `message` object of an FCM send request.
+    pub(crate) fn message(&self) -> Value {
+        self.json()["message"].clone()
+    }
+
+    /// Returns `true` for requests to the OAuth2 token endpoint.
+    pub(crate) fn is_token_request(&self) -> bool {
+        self.path == "/token"
+    }
+}
+
+/// The response a [`MockServer`] sends for a request.
+#[derive(Debug, Clone)]
+pub(crate) struct Reply {
+    status: u16,
+    body: String,
+    delay: Duration,
+}
+
+impl Reply {
+    /// A response with the given status and JSON body.
+    pub(crate) fn json(status: u16, body: Value) -> Self {
+        Self::text(status, &body.to_string())
+    }
+
+    /// A response with the given status and body.
+    pub(crate) fn text(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            body: body.to_string(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// A successful token endpoint response carrying `token`.
+    pub(crate) fn token(token: &str) -> Self {
+        Self::json(
+            200,
+            json!({"access_token": token, "expires_in": 3600, "token_type": "Bearer"}),
+        )
+    }
+
+    /// A successful FCM send response for message `id`.
+    pub(crate) fn sent(id: usize) -> Self {
+        Self::json(
+            200,
+            json!({"name": format!("projects/test-project/messages/{}", id)}),
+        )
+    }
+
+    /// An FCM error response with the given canonical status and optional FCM error code.
+    pub(crate) fn fcm_error(status: u16, error_status: &str, error_code: Option<&str>) -> Self {
+        let details: Vec<Value> = error_code
+            .map(|code| {
+                json!({
+                    "@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError",
+                    "errorCode": code,
+                })
+            })
+            .into_iter()
+            .collect();
+        Self::json(
+            status,
+            json!({"error": {
+                "code": status,
+                "message": format!("mock {}", error_status),
+                "status": error_status,
+                "details": details,
+            }}),
+        )
+    }
+
+    /// Delays the response by `delay` after the request was received.
+    pub(crate) fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+type Handler = Arc<dyn Fn(&Request) -> Reply + Send + Sync>;
+
+/// A local HTTP server that records every request and answers it with a handler.
+pub(crate) struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    /// Starts a server answering every request with `handler`.
+    pub(crate) async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> Reply + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind the mock server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Handler = Arc::new(handler);
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, handler.clone(), recorded.clone()));
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// Starts a server that issues access tokens and accepts every message, answering send
+    /// requests with consecutive message ids.
+    pub(crate) async fn fcm() -> Self {
+        Self::fcm_with(|_| None).await
+    }
+
+    /// Like [`Self::fcm`], but send requests for which `send` returns a reply get that reply.
+    pub(crate) async fn fcm_with<F>(send: F) -> Self
+    where
+        F: Fn(&Request) -> Option<Reply> + Send + Sync + 'static,
+    {
+        let sent = AtomicUsize::new(0);
+        Self::start(move |request| {
+            if request.is_token_request() {
+                return Reply::token("mock-access-token");
+            }
+            send(request).unwrap_or_else(|| Reply::sent(sent.fetch_add(1, Ordering::SeqCst)))
+        })
+        .await
+    }
+
+    /// Returns the base URL of the server, e.g. `http://127.0.0.1:4242`.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns every request received so far, in order of arrival.
+    pub(crate) fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the FCM send requests received so far, in order of arrival.
+    pub(crate) fn sends(&self) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path.ends_with("/messages:send"))
+            .collect()
+    }
+
+    /// Returns the number of token requests received so far.
+    pub(crate) fn token_requests(&self) -> usize {
+        self.requests()
+            .iter()
+            .filter(|request| request.is_token_request())
+            .count()
+    }
+
+    /// Returns a service account whose token endpoint is this server.
+    pub(crate) fn service_account(&self) -> ServiceAccount {
+        // Generating an RSA key is slow, so every test shares one.
+        static ACCOUNT: OnceLock<ServiceAccount> = OnceLock::new();
+        let mut account = ACCOUNT.get_or_init(ServiceAccount::test_dummy).clone();
+        account.token_uri = format!("{}/token", self.url);
+        account
+    }
+
+    /// Returns a service that authenticates with, sends to and manages topics on this server.
+    pub(crate) fn service(&self) -> FcmNotification {
+        FcmNotification::from_service_account(self.service_account())
+            .unwrap()
+            .with_base_url(&self.url)
+            .with_instance_id_url(&self.url)
+    }
+}
+
+/// Reads one request from `stream`, records it and writes the handler's reply.
+async fn serve(mut stream: TcpStream, handler: Handler, requests: Arc<Mutex<Vec<Request>>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+    requests.lock().unwrap().push(request.clone());
+    let reply = handler(&request);
+    tokio::time::sleep(reply.delay).await;
+
+    let response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        reply.status,
+        reply.body.len(),
+        reply.body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Reads an HTTP/1.1 request with a `Content-Length` body, or no body.
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Some(Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
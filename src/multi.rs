@@ -0,0 +1,329 @@
+//! Spreading sends across several Firebase projects.
+//!
+//! FCM quotas are enforced per project, so high-volume senders sometimes split their traffic
+//! across multiple projects. [`MultiProjectSender`] picks a project for every send and fails over
+//! to the remaining projects when one of them runs out of quota.
+
+use crate::{FcmError, FcmNotification, Message, MessageResponse, Target};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use web_time::Instant;
+
+/// How long a failure counts against a project under [`RoutingStrategy::LeastErrors`], see
+/// [`MultiProjectSender::with_error_window`].
+pub const DEFAULT_ERROR_WINDOW: Duration = Duration::from_secs(60);
+
+/// The most failures remembered per project; older ones are forgotten first.
+const MAX_TRACKED_ERRORS: usize = 1024;
+
+/// How [`MultiProjectSender`] chooses the project that gets the first attempt for a send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoutingStrategy {
+    /// Cycle through the projects in order.
+    #[default]
+    RoundRobin,
+    /// Prefer the project with the fewest recent failures, cycling through the projects that
+    /// are tied.
+    ///
+    /// Only failures caused by the project count: server errors, quota and authentication
+    /// errors, and timeouts or connection failures. A message FCM rejects, e.g. for an
+    /// unregistered token, says nothing about the project. Failures are forgotten once they are
+    /// older than the [error window](MultiProjectSender::with_error_window), so a project that
+    /// recovers gets traffic again.
+    LeastErrors,
+    /// Always route the same target to the same project, e.g. for per-project analytics.
+    ///
+    /// The target (token, topic or condition) is hashed with a fixed algorithm, so the mapping
+    /// is stable across process restarts and machines. Adding a project moves only about
+    /// `1 / n` of the targets to it. Failover on quota errors still applies.
+    ConsistentHash,
+}
+
+/// A sender that distributes messages over several [`FcmNotification`] services.
+///
+/// When a project rejects a send because its quota is exhausted (HTTP 429 / `RESOURCE_EXHAUSTED`),
+/// the message is retried on the next project. Any other error is returned immediately.
+pub struct MultiProjectSender {
+    services: Vec<FcmNotification>,
+    strategy: RoutingStrategy,
+    next: AtomicUsize,
+    /// When each project's recent failures happened, oldest first.
+    errors: Vec<Mutex<VecDeque<Instant>>>,
+    error_window: Duration,
+}
+
+impl MultiProjectSender {
+    /// Creates a sender that round-robins over `services`.
+    ///
+    /// # Panics
+    /// Panics if `services` is empty.
+    pub fn new(services: Vec<FcmNotification>) -> Self {
+        assert!(
+            !services.is_empty(),
+            "MultiProjectSender requires at least one service"
+        );
+        let errors = services.iter().map(|_| Mutex::default()).collect();
+
+        Self {
+            services,
+            strategy: RoutingStrategy::default(),
+            next: AtomicUsize::new(0),
+            errors,
+            error_window: DEFAULT_ERROR_WINDOW,
+        }
+    }
+
+    /// Sets the strategy used to pick the first project for each send.
+    pub fn with_strategy(mut self, strategy: RoutingStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets how long a failure counts against a project under [`RoutingStrategy::LeastErrors`].
+    ///
+    /// Defaults to [`DEFAULT_ERROR_WINDOW`].
+    pub fn with_error_window(mut self, window: Duration) -> Self {
+        self.error_window = window;
+        self
+    }
+
+    /// Returns the services this sender distributes messages over.
+    pub fn services(&self) -> &[FcmNotification] {
+        &self.services
+    }
+
+    /// Sends a message through one of the projects, failing over on quota errors.
+    ///
+    /// # Arguments
+    /// * `message` - The message to send.
+    ///
+    /// # Errors
+    /// Returns the first non-quota error, or the last quota error if every project is exhausted.
+    pub async fn send(&self, message: &Message) -> Result<MessageResponse, FcmError> {
+        let start = self.pick(&message.target);
+        let mut last_error = None;
+
+        for offset in 0..self.services.len() {
+            let index = (start + offset) % self.services.len();
+            match self.services[index].send(message).await {
+                Ok(response) => return Ok(response),
+                Err(error) => {
+                    if error.is_retryable() || error.is_auth_error() {
+                        self.record_error(index);
+                    }
+                    if !error.is_quota_exceeded() {
+                        return Err(error);
+                    }
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(last_error.expect("at least one service was attempted"))
+    }
+
+    /// Returns the index of the service that gets the first attempt at sending to `target`.
+    fn pick(&self, target: &Target) -> usize {
+        match self.strategy {
+            RoutingStrategy::RoundRobin => {
+                self.next.fetch_add(1, Ordering::Relaxed) % self.services.len()
+            }
+            RoutingStrategy::LeastErrors => {
+                let counts: Vec<usize> = (0..self.services.len())
+                    .map(|index| self.recent_errors(index))
+                    .collect();
+                let fewest = counts.iter().copied().min().unwrap_or(0);
+                let tied: Vec<usize> = (0..counts.len())
+                    .filter(|&index| counts[index] == fewest)
+                    .collect();
+                tied[self.next.fetch_add(1, Ordering::Relaxed) % tied.len()]
+            }
+            RoutingStrategy::ConsistentHash => {
+                let key = match target {
+                    Target::Token(key) | Target::Topic(key) | Target::Condition(key) => key,
+                };
+                jump_consistent_hash(fnv1a(key.as_bytes()), self.services.len())
+            }
+        }
+    }
+
+    /// Remembers a failure of the project at `index`.
+    fn record_error(&self, index: usize) {
+        let mut errors = self.errors[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if errors.len() == MAX_TRACKED_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(Instant::now());
+    }
+
+    /// Returns the number of failures of the project at `index` within the error window,
+    /// forgetting older ones.
+    fn recent_errors(&self, index: usize) -> usize {
+        let mut errors = self.errors[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        while errors
+            .front()
+            .is_some_and(|failed_at| failed_at.elapsed() >= self.error_window)
+        {
+            errors.pop_front();
+        }
+        errors.len()
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike std's hashers is stable across releases and processes.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Maps `key` to one of `buckets` buckets with Lamping and Veach's jump consistent hash.
+fn jump_consistent_hash(mut key: u64, buckets: usize) -> usize {
+    let (mut bucket, mut next) = (0i64, 0i64);
+    while next < buckets as i64 {
+        bucket = next;
+        key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+    bucket as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockServer, Reply};
+
+    /// Sends `count` messages and returns how many reached each server.
+    async fn spread(
+        sender: &MultiProjectSender,
+        servers: &[MockServer],
+        count: usize,
+    ) -> Vec<usize> {
+        for _ in 0..count {
+            let _ = sender
+                .send(&Message::notification("device-token", "Hello", "World"))
+                .await;
+        }
+        servers.iter().map(|server| server.sends().len()).collect()
+    }
+
+    fn least_errors(servers: &[MockServer]) -> MultiProjectSender {
+        MultiProjectSender::new(servers.iter().map(MockServer::service).collect())
+            .with_strategy(RoutingStrategy::LeastErrors)
+    }
+
+    #[tokio::test]
+    async fn round_robin_cycles_through_the_projects() {
+        let servers = [MockServer::fcm().await, MockServer::fcm().await];
+        let sender = MultiProjectSender::new(servers.iter().map(MockServer::service).collect());
+
+        assert_eq!(spread(&sender, &servers, 4).await, [2, 2]);
+    }
+
+    #[tokio::test]
+    async fn quota_errors_fail_over_to_the_next_project() {
+        let servers = [
+            MockServer::fcm_with(|_| {
+                Some(Reply::fcm_error(
+                    429,
+                    "RESOURCE_EXHAUSTED",
+                    Some("QUOTA_EXCEEDED"),
+                ))
+            })
+            .await,
+            MockServer::fcm().await,
+        ];
+        let sender = MultiProjectSender::new(
+            servers
+                .iter()
+                .map(|server| {
+                    server
+                        .service()
+                        .with_retry_policy(crate::RetryPolicy::none())
+                })
+                .collect(),
+        );
+
+        let response = sender
+            .send(&Message::notification("device-token", "Hello", "World"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.name, "projects/test-project/messages/0");
+        assert_eq!(servers[0].sends().len(), 1);
+        assert_eq!(servers[1].sends().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn least_errors_breaks_ties_round_robin() {
+        let servers = [MockServer::fcm().await, MockServer::fcm().await];
+        let sender = least_errors(&servers);
+
+        assert_eq!(spread(&sender, &servers, 4).await, [2, 2]);
+    }
+
+    #[tokio::test]
+    async fn least_errors_avoids_failing_projects() {
+        let servers = [
+            MockServer::fcm_with(|_| Some(Reply::fcm_error(500, "INTERNAL", None))).await,
+            MockServer::fcm().await,
+        ];
+        let sender = MultiProjectSender::new(
+            servers
+                .iter()
+                .map(|server| {
+                    server
+                        .service()
+                        .with_retry_policy(crate::RetryPolicy::none())
+                })
+                .collect(),
+        )
+        .with_strategy(RoutingStrategy::LeastErrors);
+
+        assert_eq!(spread(&sender, &servers, 4).await, [1, 3]);
+    }
+
+    #[tokio::test]
+    async fn least_errors_ignores_rejected_messages() {
+        let servers = [
+            MockServer::fcm_with(|_| {
+                Some(Reply::fcm_error(404, "NOT_FOUND", Some("UNREGISTERED")))
+            })
+            .await,
+            MockServer::fcm().await,
+        ];
+        let sender = least_errors(&servers);
+
+        assert_eq!(spread(&sender, &servers, 4).await, [2, 2]);
+    }
+
+    #[tokio::test]
+    async fn least_errors_forgets_old_failures() {
+        let servers = [
+            MockServer::fcm_with(|_| Some(Reply::fcm_error(500, "INTERNAL", None))).await,
+            MockServer::fcm().await,
+        ];
+        let sender = MultiProjectSender::new(
+            servers
+                .iter()
+                .map(|server| {
+                    server
+                        .service()
+                        .with_retry_policy(crate::RetryPolicy::none())
+                })
+                .collect(),
+        )
+        .with_strategy(RoutingStrategy::LeastErrors)
+        .with_error_window(Duration::from_millis(100));
+
+        assert_eq!(spread(&sender, &servers, 2).await, [1, 1]);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(spread(&sender, &servers, 2).await, [2, 2]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Buffering messages emitted one at a time and sending them in batches.
+
+use crate::{BatchResult, FcmNotification, Message, MAX_BATCH_SIZE};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Arc, Weak};
+use std::sync::{Mutex, MutexGuard};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::sync::mpsc;
+
+/// A buffer that collects messages and sends them together with
+/// [`FcmNotification::send_batch`].
+///
+/// Messages are sent once [`max_size`](Self::with_max_size) of them are pending, when
+/// [`flush`](Self::flush) is called, or periodically with
+/// [`spawn_auto_flush`](Self::spawn_auto_flush). Batching amortizes the access token lookup and
+/// keeps the batch concurrency saturated for apps that produce notifications one by one.
+///
+/// Pending messages are lost if the queue is dropped without flushing.
+pub struct BatchQueue {
+    service: FcmNotification,
+    pending: Mutex<Vec<Message>>,
+    max_size: usize,
+}
+
+impl BatchQueue {
+    /// Creates an empty queue that sends through `service`, flushing at [`MAX_BATCH_SIZE`].
+    pub fn new(service: FcmNotification) -> Self {
+        Self {
+            service,
+            pending: Mutex::new(Vec::new()),
+            max_size: MAX_BATCH_SIZE,
+        }
+    }
+
+    /// Sets the number of pending messages that triggers a flush, between 1 and
+    /// [`MAX_BATCH_SIZE`].
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size.clamp(1, MAX_BATCH_SIZE);
+        self
+    }
+
+    /// Returns the number of messages waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no messages are waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Adds a message to the queue, flushing it if it is now full.
+    ///
+    /// Returns the outcomes of the flushed batch, in enqueue order, if this call triggered a
+    /// flush.
+    ///
+    /// # Arguments
+    /// * `message` - The message to send with the next batch.
+    pub async fn enqueue(&self, message: Message) -> Option<Vec<BatchResult>> {
+        let batch = {
+            let mut pending = self.lock();
+            pending.push(message);
+            if pending.len() < self.max_size {
+                return None;
+            }
+            std::mem::take(&mut *pending)
+        };
+        Some(self.service.send_batch(batch).await)
+    }
+
+    /// Sends every pending message now and returns the outcomes in enqueue order.
+    pub async fn flush(&self) -> Vec<BatchResult> {
+        let batch = std::mem::take(&mut *self.lock());
+        if batch.is_empty() {
+            return Vec::new();
+        }
+        self.service.send_batch(batch).await
+    }
+
+    /// Spawns a background task that flushes the queue every `interval`.
+    ///
+    /// The outcomes of each non-empty flush are delivered on the returned channel. The task
+    /// stops once the queue or the receiver has been dropped; until the receiver takes a
+    /// batch of outcomes, no further periodic flushes happen.
+    ///
+    /// # Panics
+    /// Panics if `interval` is zero or if called outside of a Tokio runtime.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_auto_flush(
+        self: &Arc<Self>,
+        interval: Duration,
+    ) -> mpsc::Receiver<Vec<BatchResult>> {
+        let (result_tx, result_rx) = mpsc::channel(1);
+        let queue: Weak<Self> = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            while !result_tx.is_closed() {
+                ticker.tick().await;
+                let Some(queue) = queue.upgrade() else {
+                    break;
+                };
+                let results = queue.flush().await;
+                drop(queue);
+                if !results.is_empty() && result_tx.send(results).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        result_rx
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Message>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    fn message(index: usize) -> Message {
+        Message::notification(&format!("token-{}", index), "Hello", "World")
+    }
+
+    #[tokio::test]
+    async fn full_queues_flush_themselves() {
+        let server = MockServer::fcm().await;
+        let queue = BatchQueue::new(server.service()).with_max_size(3);
+
+        assert!(queue.enqueue(message(0)).await.is_none());
+        assert!(queue.enqueue(message(1)).await.is_none());
+        assert_eq!(queue.len(), 2);
+        assert!(server.sends().is_empty());
+
+        let results = queue.enqueue(message(2)).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        assert!(queue.is_empty());
+        assert_eq!(server.sends().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn flush_sends_what_is_pending() {
+        let server = MockServer::fcm().await;
+        let queue = BatchQueue::new(server.service());
+
+        assert!(queue.flush().await.is_empty());
+        queue.enqueue(message(0)).await;
+        queue.enqueue(message(1)).await;
+
+        let results = queue.flush().await;
+        assert_eq!(results.len(), 2);
+        assert!(queue.is_empty());
+        assert_eq!(server.sends().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn auto_flush_delivers_the_outcomes() {
+        let server = MockServer::fcm().await;
+        let queue = Arc::new(BatchQueue::new(server.service()));
+        let mut outcomes = queue.spawn_auto_flush(Duration::from_millis(20));
+
+        queue.enqueue(message(0)).await;
+        let results = tokio::time::timeout(Duration::from_secs(5), outcomes.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+//! Sending without interpreting the FCM response.
+
+use crate::{FcmApiError, FcmError, FcmNotification, Message, SendOptions};
+use serde::de::DeserializeOwned;
+
+/// The unprocessed HTTP response of an FCM send request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawResponse {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response body, usually JSON.
+    ///
+    /// The body of a non-2xx response is cut off at
+    /// [`with_max_error_body_size`](FcmNotification::with_max_error_body_size).
+    pub body: String,
+}
+
+impl RawResponse {
+    /// Returns `true` if the status code is in the 2xx range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+impl FcmNotification {
+    /// Sends a message and returns FCM's response as-is, whatever its status code.
+    ///
+    /// This is meant for callers with their own error policy, e.g. proxies that pass FCM's
+    /// answer straight through to their clients. A non-2xx response is **not** turned into an
+    /// error, and it is not retried or reported to the hooks: the caller is responsible for
+    /// inspecting [`RawResponse::status`] and the body.
+    ///
+    /// # Arguments
+    /// * `message` - The message to send, including its target.
+    /// * `options` - Options that only apply to this call.
+    ///
+    /// # Errors
+    /// Only returns an error if the message fails the local checks, including
+    /// [`FcmError::PayloadTooLarge`], the access token cannot be retrieved, or the HTTP request
+    /// itself fails.
+    pub async fn send_raw(
+        &self,
+        message: &Message,
+        options: &SendOptions,
+    ) -> Result<RawResponse, FcmError> {
+        let message = self.with_defaults(message);
+        message.check()?;
+        let message = Message::into_prepared(message);
+        let body = crate::encode_checked_request(message.as_ref(), options.validate_only)?;
+        let access_token = self.get_access_token().await?;
+
+        let _permit = self.acquire_send_permit().await;
+        let response = self
+            .post(&self.send_url(options))
+            .header("Authorization", format!("Bearer {}", access_token))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = if status.is_success() {
+            response.text().await?
+        } else {
+            crate::read_capped(response, self.max_error_body_size).await?
+        };
+        Ok(RawResponse {
+            status: status.as_u16(),
+            body,
+        })
+    }
+
+    /// Sends a message and deserializes FCM's successful response into `T`.
+    ///
+    /// This allows reading response fields [`MessageResponse`](crate::MessageResponse) doesn't
+    /// know about yet. Like [`send_raw`](Self::send_raw), the request is not retried or reported
+    /// to the hooks, but a non-2xx response is turned into [`FcmError::ApiError`].
+    ///
+    /// # Arguments
+    /// * `message` - The message to send, including its target.
+    /// * `options` - Options that only apply to this call.
+    ///
+    /// # Errors
+    /// Returns an error if [`send_raw`](Self::send_raw) fails, if FCM rejects the message, or
+    /// [`FcmError::ResponseParseError`] if the response body doesn't match `T`.
+    pub async fn send_raw_as<T: DeserializeOwned>(
+        &self,
+        message: &Message,
+        options: &SendOptions,
+    ) -> Result<T, FcmError> {
+        let response = self.send_raw(message, options).await?;
+        if !response.is_success() {
+            return Err(FcmError::ApiError(FcmApiError::from_response(
+                response.status,
+                &response.body,
+            )));
+        }
+        serde_json::from_str(&response.body).map_err(|source| FcmError::ResponseParseError {
+            body: response.body,
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{MockServer, Reply},
+        MAX_MESSAGE_SIZE,
+    };
+
+    #[tokio::test]
+    async fn raw_responses_are_returned_as_is() {
+        let server = MockServer::fcm_with(|_| {
+            Some(Reply::fcm_error(404, "NOT_FOUND", Some("UNREGISTERED")))
+        })
+        .await;
+        let service = server.service();
+
+        let response = service
+            .send_raw(
+                &Message::notification("device-token", "Hello", "World"),
+                &SendOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, 404);
+        assert!(!response.is_success());
+        assert!(response.body.contains("UNREGISTERED"));
+        assert_eq!(server.sends()[0].message()["token"], "device-token");
+    }
+
+    #[tokio::test]
+    async fn raw_error_bodies_are_capped() {
+        let server = MockServer::fcm_with(|_| Some(Reply::text(500, &"x".repeat(100)))).await;
+        let service = server.service().with_max_error_body_size(10);
+
+        let response = service
+            .send_raw(
+                &Message::notification("device-token", "Hello", "World"),
+                &SendOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.body,
+            format!("{}{}", "x".repeat(10), crate::TRUNCATION_MARKER)
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_raw_sends_are_rejected_locally() {
+        let server = MockServer::fcm().await;
+        let service = server.service();
+        let message = Message::notification("device-token", "Hello", &"x".repeat(MAX_MESSAGE_SIZE));
+
+        let error = service
+            .send_raw(&message, &SendOptions::default())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, FcmError::PayloadTooLarge { .. }));
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn raw_responses_are_deserialized() {
+        #[derive(serde::Deserialize)]
+        struct Sent {
+            name: String,
+        }
+
+        let server = MockServer::fcm().await;
+        let service = server.service();
+
+        let sent: Sent = service
+            .send_raw_as(
+                &Message::notification("device-token", "Hello", "World"),
+                &SendOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(sent.name, "projects/test-project/messages/0");
+    }
+}
@@ -0,0 +1,207 @@
+//! Retrying transient FCM failures.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// Decides how long to wait before each retry of a failed send, and when to give up.
+///
+/// [`RetryPolicy`] implements exponential backoff with jitter, which is what
+/// [`FcmNotification::with_retry_policy`](crate::FcmNotification::with_retry_policy) uses.
+/// Other strategies, e.g. constant or linear delays, can be plugged in with
+/// [`FcmNotification::with_backoff`](crate::FcmNotification::with_backoff). Closures taking the
+/// attempt implement this trait too.
+///
+/// Whether a failure is retried at all is decided before the backoff is asked, see
+/// [`FcmNotification::with_retry_if`](crate::FcmNotification::with_retry_if), and the
+/// [`RetryBudget`] may still veto a retry.
+pub trait Backoff: Send + Sync {
+    /// Returns the delay to wait before retry number `attempt` (starting at 0), or `None` to
+    /// stop retrying and return the last error.
+    fn next_delay(&self, attempt: u32) -> Option<Duration>;
+}
+
+impl<F> Backoff for F
+where
+    F: Fn(u32) -> Option<Duration> + Send + Sync,
+{
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        self(attempt)
+    }
+}
+
+/// How often and how patiently a failed send is retried.
+///
+/// Only transient failures are retried; see [`FcmError::is_retryable`](crate::FcmError::is_retryable).
+/// The delay before retry `n` (starting at 0) is `initial_backoff * 2^n`, capped at `max_backoff`,
+/// with random jitter of up to half the delay so that many senders don't retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt.
+    pub max_retries: u32,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The upper bound for any single delay.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries. This is what a new service uses.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the delay to wait before retry number `attempt` (starting at 0).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let base = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        base / 2 + base.mul_f64(random_fraction() / 2.0)
+    }
+}
+
+impl Backoff for RetryPolicy {
+    /// Returns [`backoff`](Self::backoff) for the first `max_retries` retries.
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        (attempt < self.max_retries).then(|| self.backoff(attempt))
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three retries, starting at 500ms and capped at 30s.
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A token bucket limiting retries across all sends of a service.
+///
+/// This follows gRPC's retry throttling: the bucket starts full with `max_tokens`, every
+/// retryable failure removes one token and every success adds back `token_ratio` tokens. Retries
+/// are only attempted while more than half of the tokens remain, so during a widespread outage
+/// sends fail fast instead of multiplying the load with retries.
+#[derive(Debug)]
+pub struct RetryBudget {
+    max_tokens: f64,
+    token_ratio: f64,
+    tokens: Mutex<f64>,
+}
+
+impl RetryBudget {
+    /// Creates a budget holding `max_tokens`, refilled by `token_ratio` per successful send.
+    pub fn new(max_tokens: f64, token_ratio: f64) -> Self {
+        Self {
+            max_tokens,
+            token_ratio,
+            tokens: Mutex::new(max_tokens),
+        }
+    }
+
+    /// Returns `true` if the budget currently permits a retry.
+    pub fn allows_retry(&self) -> bool {
+        *self.lock() > self.max_tokens / 2.0
+    }
+
+    /// Refills the bucket after a successful send.
+    pub(crate) fn record_success(&self) {
+        let mut tokens = self.lock();
+        *tokens = (*tokens + self.token_ratio).min(self.max_tokens);
+    }
+
+    /// Drains the bucket after a retryable failure.
+    pub(crate) fn record_failure(&self) {
+        let mut tokens = self.lock();
+        *tokens = (*tokens - 1.0).max(0.0);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, f64> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for RetryBudget {
+    /// Ten tokens with a ratio of 0.1: ten successes pay for one retry.
+    fn default() -> Self {
+        Self::new(10.0, 0.1)
+    }
+}
+
+/// Returns a pseudo-random number in `[0, 1)`, good enough for jitter.
+fn random_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(nanos);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_within_jitter_and_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(1000),
+        };
+
+        for (attempt, base) in [(0, 100), (1, 200), (2, 400), (3, 800), (4, 1000), (9, 1000)] {
+            let delay = policy.backoff(attempt);
+            let base = Duration::from_millis(base);
+            assert!(
+                delay >= base / 2 && delay <= base,
+                "{:?} at attempt {}",
+                delay,
+                attempt
+            );
+        }
+    }
+
+    #[test]
+    fn budget_blocks_retries_until_successes_refill_it() {
+        let budget = RetryBudget::new(4.0, 0.5);
+        assert!(budget.allows_retry());
+
+        budget.record_failure();
+        assert!(budget.allows_retry());
+        budget.record_failure();
+        assert!(!budget.allows_retry());
+
+        budget.record_success();
+        assert!(budget.allows_retry());
+        for _ in 0..10 {
+            budget.record_success();
+        }
+        budget.record_failure();
+        assert!(budget.allows_retry());
+    }
+
+    #[test]
+    fn policy_stops_after_max_retries() {
+        let policy = RetryPolicy {
+            max_retries: 2,
+            ..RetryPolicy::default()
+        };
+
+        assert!(policy.next_delay(0).is_some());
+        assert!(policy.next_delay(1).is_some());
+        assert!(policy.next_delay(2).is_none());
+        assert!(RetryPolicy::none().next_delay(0).is_none());
+    }
+}
@@ -0,0 +1,53 @@
+//! Credentials for tests that should not need a real Firebase project.
+
+use crate::ServiceAccount;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+
+impl ServiceAccount {
+    /// Returns a well-formed service account with a freshly generated 2048-bit RSA key.
+    ///
+    /// The key really signs JWTs, so the whole token path can be exercised: point `token_uri` at
+    /// a mock OAuth2 endpoint and build the service with
+    /// [`FcmNotification::from_service_account`](crate::FcmNotification::from_service_account).
+    /// Google will of course reject tokens signed with it.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use fcm_notification::{FcmNotification, ServiceAccount};
+    /// let mut account = ServiceAccount::test_dummy();
+    /// account.token_uri = "http://127.0.0.1:8080/token".to_string();
+    /// assert!(account.validate_private_key().is_ok());
+    ///
+    /// let key = jsonwebtoken::EncodingKey::from_rsa_pem(account.private_key.as_bytes()).unwrap();
+    /// let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+    /// assert!(jsonwebtoken::encode(&header, &serde_json::json!({ "sub": "test" }), &key).is_ok());
+    ///
+    /// let service = FcmNotification::from_service_account(account).unwrap();
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if OpenSSL fails to generate the key.
+    pub fn test_dummy() -> Self {
+        let key = Rsa::generate(2048)
+            .and_then(PKey::from_rsa)
+            .and_then(|key| key.private_key_to_pem_pkcs8())
+            .expect("failed to generate a test RSA key");
+
+        Self {
+            account_type: "service_account".to_string(),
+            project_id: "test-project".to_string(),
+            private_key_id: "test-key-id".to_string(),
+            private_key: String::from_utf8(key).expect("PEM is ASCII"),
+            client_email: "test@test-project.iam.gserviceaccount.com".to_string(),
+            client_id: "000000000000000000000".to_string(),
+            auth_uri: "https://accounts.google.com/o/oauth2/auth".to_string(),
+            token_uri: "https://oauth2.googleapis.com/token".to_string(),
+            auth_provider_x509_cert_url: "https://www.googleapis.com/oauth2/v1/certs".to_string(),
+            client_x509_cert_url:
+                "https://www.googleapis.com/robot/v1/metadata/x509/test%40test-project.iam.gserviceaccount.com"
+                    .to_string(),
+            universe_domain: "googleapis.com".to_string(),
+        }
+    }
+}
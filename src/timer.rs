@@ -0,0 +1,36 @@
+//! Timers that work both on Tokio and on wasm32, where Tokio has no time driver.
+//!
+//! On wasm32 the timers are backed by the host's `setTimeout`, which browsers and Cloudflare
+//! Workers provide.
+
+use std::future::Future;
+use std::time::Duration;
+
+/// Waits until `duration` has elapsed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// Waits until `duration` has elapsed.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await;
+}
+
+/// Runs `future` for at most `duration`, returning `None` if it didn't complete in time.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// Runs `future` for at most `duration`, returning `None` if it didn't complete in time.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    use futures_util::future::{select, Either};
+
+    match select(std::pin::pin!(future), std::pin::pin!(sleep(duration))).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
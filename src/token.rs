@@ -0,0 +1,423 @@
+//! Pluggable access token acquisition.
+//!
+//! By default [`FcmNotification`](crate::FcmNotification) signs a JWT with the service account key
+//! and exchanges it for an access token. Environments without a key file, such as GKE with
+//! Workload Identity, can supply tokens through a [`TokenProvider`] instead.
+
+use crate::FcmError;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// The OAuth2 scope required to send messages with FCM.
+pub const FIREBASE_MESSAGING_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+
+/// The scopes of the access tokens used for sending.
+pub(crate) const MESSAGING_SCOPES: &[&str] = &[FIREBASE_MESSAGING_SCOPE];
+
+/// How long an access token is assumed to be valid when the token endpoint doesn't say.
+///
+/// Google's tokens currently last an hour; half of that keeps the cache on the safe side.
+const FALLBACK_TOKEN_LIFETIME: chrono::Duration = chrono::Duration::minutes(30);
+
+/// The successful response of an OAuth2 token endpoint or the metadata server.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TokenResponse {
+    pub(crate) access_token: String,
+    /// The lifetime of the token in seconds.
+    pub(crate) expires_in: Option<u64>,
+    pub(crate) token_type: String,
+}
+
+impl TokenResponse {
+    /// Converts the response into a token issued at `issued_at`.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::TokenProviderError`] if the token is not a bearer token.
+    pub(crate) fn into_access_token(
+        self,
+        issued_at: DateTime<Utc>,
+    ) -> Result<AccessToken, FcmError> {
+        if !self.token_type.eq_ignore_ascii_case("bearer") {
+            return Err(FcmError::TokenProviderError(format!(
+                "expected a bearer token, got token type `{}`",
+                self.token_type
+            )));
+        }
+        let lifetime = self
+            .expires_in
+            .filter(|seconds| *seconds > 0)
+            .and_then(|seconds| i64::try_from(seconds).ok())
+            .map_or(FALLBACK_TOKEN_LIFETIME, chrono::Duration::seconds);
+        Ok(AccessToken {
+            token: self.access_token,
+            expires_at: issued_at + lifetime,
+        })
+    }
+}
+
+/// An OAuth2 access token together with the time it stops being valid.
+///
+/// It serializes as `{"token": "ya29...", "expires_at": "2024-05-01T12:00:00Z"}`, with the expiry
+/// in RFC 3339, so a [`TokenStore`] can keep it as JSON, e.g. in Redis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessToken {
+    /// The bearer token sent in the `Authorization` header.
+    pub token: String,
+    /// The time at which the token expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AccessToken {
+    /// Tokens are refreshed slightly before they expire so in-flight requests don't race the expiry.
+    pub(crate) fn is_fresh(&self) -> bool {
+        self.expires_at - chrono::Duration::seconds(60) > Utc::now()
+    }
+}
+
+/// What the service's current access token is good for, see
+/// [`FcmNotification::token_info`](crate::FcmNotification::token_info).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    /// The project messages are sent for.
+    pub project_id: String,
+    /// The OAuth2 scopes the token was requested with.
+    pub scopes: Vec<String>,
+    /// The time at which the token expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A source of access tokens for the FCM API.
+///
+/// Tokens returned by a provider are cached by the service until shortly before their
+/// `expires_at`, so implementations don't need to cache themselves. The token must carry the
+/// [`FIREBASE_MESSAGING_SCOPE`] scope.
+///
+/// A provider is not told which scopes a request needs, so the same token is used for every
+/// API. To manage topic subscriptions with it, the token must also carry the
+/// `https://www.googleapis.com/auth/cloud-platform` scope the Instance ID API requires;
+/// otherwise those requests fail with an authentication error while sends succeed.
+pub trait TokenProvider: Send + Sync {
+    /// Fetches a new access token.
+    fn fetch_token(&self) -> BoxFuture<'_, Result<AccessToken, FcmError>>;
+}
+
+/// Shared storage for the cached access token, e.g. in Redis.
+///
+/// Every [`FcmNotification`](crate::FcmNotification) keeps its token in memory. With a store
+/// configured through [`with_token_store`](crate::FcmNotification::with_token_store), a service
+/// that has no fresh token first looks in the store and only fetches a new one if the store has
+/// none either, then saves it there. Worker processes sharing a store thus share one token
+/// until it expires instead of each requesting their own.
+///
+/// A store should only be shared by services using the same credentials. Failing store calls
+/// are logged and otherwise ignored, so a store outage never stops sends.
+pub trait TokenStore: Send + Sync {
+    /// Returns the stored token, if any. It may have expired.
+    fn load(&self) -> BoxFuture<'_, Result<Option<AccessToken>, FcmError>>;
+
+    /// Replaces the stored token.
+    fn save(&self, token: AccessToken) -> BoxFuture<'_, Result<(), FcmError>>;
+
+    /// Removes the stored token, e.g. after the credentials were reloaded.
+    fn clear(&self) -> BoxFuture<'_, Result<(), FcmError>>;
+}
+
+impl<T: TokenStore + ?Sized> TokenStore for std::sync::Arc<T> {
+    fn load(&self) -> BoxFuture<'_, Result<Option<AccessToken>, FcmError>> {
+        (**self).load()
+    }
+
+    fn save(&self, token: AccessToken) -> BoxFuture<'_, Result<(), FcmError>> {
+        (**self).save(token)
+    }
+
+    fn clear(&self) -> BoxFuture<'_, Result<(), FcmError>> {
+        (**self).clear()
+    }
+}
+
+/// A [`TokenStore`] that keeps the token in memory, for sharing it between services in one
+/// process through an `Arc<MemoryTokenStore>`.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl MemoryTokenStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<AccessToken>> {
+        self.token
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> BoxFuture<'_, Result<Option<AccessToken>, FcmError>> {
+        let token = self.lock().clone();
+        Box::pin(async move { Ok(token) })
+    }
+
+    fn save(&self, token: AccessToken) -> BoxFuture<'_, Result<(), FcmError>> {
+        *self.lock() = Some(token);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn clear(&self) -> BoxFuture<'_, Result<(), FcmError>> {
+        *self.lock() = None;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A [`TokenProvider`] backed by the GCE/GKE metadata server.
+///
+/// On Google Compute Engine, Cloud Run and GKE with Workload Identity, the metadata server hands
+/// out tokens for the service account attached to the workload. It is not available on wasm32.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct MetadataServerTokenProvider {
+    client: Client,
+    account: String,
+    /// The `computeMetadata/v1` endpoint, [`Self::BASE_URL`] outside of tests.
+    base_url: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MetadataServerTokenProvider {
+    const BASE_URL: &'static str = "http://metadata.google.internal/computeMetadata/v1";
+
+    /// Creates a provider for the workload's default service account.
+    pub fn new() -> Self {
+        Self::for_account("default")
+    }
+
+    /// Creates a provider for a specific service account attached to the workload.
+    pub fn for_account(account: &str) -> Self {
+        Self {
+            client: Client::new(),
+            account: account.to_string(),
+            base_url: Self::BASE_URL.to_string(),
+        }
+    }
+
+    /// Returns the id of the project the workload runs in.
+    ///
+    /// # Errors
+    /// Returns an error if the metadata server cannot be reached.
+    pub async fn project_id(&self) -> Result<String, FcmError> {
+        let response = self
+            .client
+            .get(format!("{}/project/project-id", self.base_url))
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.text().await?.trim().to_string())
+    }
+
+    async fn fetch(&self) -> Result<AccessToken, FcmError> {
+        let now = Utc::now();
+        let response = self
+            .client
+            .get(format!(
+                "{}/instance/service-accounts/{}/token",
+                self.base_url, self.account
+            ))
+            .query(&[("scopes", FIREBASE_MESSAGING_SCOPE)])
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?;
+        let body = response.text().await?;
+        let response: TokenResponse = serde_json::from_str(&body)
+            .map_err(|source| FcmError::ResponseParseError { body, source })?;
+
+        response.into_access_token(now)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for MetadataServerTokenProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TokenProvider for MetadataServerTokenProvider {
+    fn fetch_token(&self) -> BoxFuture<'_, Result<AccessToken, FcmError>> {
+        Box::pin(self.fetch())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockServer, Reply};
+    use crate::FcmNotification;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Hands out `provided-token-<n>` tokens that expire after `lifetime`.
+    struct CountingProvider {
+        fetches: AtomicUsize,
+        lifetime: chrono::Duration,
+    }
+
+    impl TokenProvider for Arc<CountingProvider> {
+        fn fetch_token(&self) -> BoxFuture<'_, Result<AccessToken, FcmError>> {
+            let fetch = self.fetches.fetch_add(1, Ordering::SeqCst);
+            let token = AccessToken {
+                token: format!("provided-token-{}", fetch),
+                expires_at: Utc::now() + self.lifetime,
+            };
+            Box::pin(async move { Ok(token) })
+        }
+    }
+
+    fn provider(lifetime: chrono::Duration) -> Arc<CountingProvider> {
+        Arc::new(CountingProvider {
+            fetches: AtomicUsize::new(0),
+            lifetime,
+        })
+    }
+
+    #[test]
+    fn tokens_about_to_expire_are_not_fresh() {
+        let token = |lifetime| AccessToken {
+            token: "token".to_string(),
+            expires_at: Utc::now() + lifetime,
+        };
+
+        assert!(token(chrono::Duration::minutes(5)).is_fresh());
+        assert!(!token(chrono::Duration::seconds(30)).is_fresh());
+    }
+
+    #[tokio::test]
+    async fn provider_tokens_are_cached_until_they_expire() {
+        let fresh = provider(chrono::Duration::hours(1));
+        let service = FcmNotification::from_token_provider("test-project", fresh.clone()).unwrap();
+        assert_eq!(
+            service.get_access_token().await.unwrap(),
+            "provided-token-0"
+        );
+        assert_eq!(
+            service.get_access_token().await.unwrap(),
+            "provided-token-0"
+        );
+        assert_eq!(fresh.fetches.load(Ordering::SeqCst), 1);
+
+        let expiring = provider(chrono::Duration::seconds(30));
+        let service =
+            FcmNotification::from_token_provider("test-project", expiring.clone()).unwrap();
+        service.get_access_token().await.unwrap();
+        service.get_access_token().await.unwrap();
+        assert_eq!(expiring.fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn services_sharing_a_store_share_the_token() {
+        let provider = provider(chrono::Duration::hours(1));
+        let store = Arc::new(MemoryTokenStore::new());
+
+        for _ in 0..3 {
+            let service = FcmNotification::from_token_provider("test-project", provider.clone())
+                .unwrap()
+                .with_token_store(store.clone());
+            assert_eq!(
+                service.get_access_token().await.unwrap(),
+                "provided-token-0"
+            );
+        }
+
+        assert_eq!(provider.fetches.load(Ordering::SeqCst), 1);
+        let stored = store.load().await.unwrap().unwrap();
+        assert_eq!(stored.token, "provided-token-0");
+    }
+
+    #[test]
+    fn token_responses_default_their_lifetime() {
+        let issued_at = Utc::now();
+        let response = TokenResponse {
+            access_token: "token".to_string(),
+            expires_in: None,
+            token_type: "Bearer".to_string(),
+        };
+
+        let token = response.into_access_token(issued_at).unwrap();
+        assert_eq!(token.expires_at, issued_at + FALLBACK_TOKEN_LIFETIME);
+    }
+
+    #[test]
+    fn access_tokens_round_trip_through_json() {
+        let token = AccessToken {
+            token: "ya29.token".to_string(),
+            expires_at: "2024-05-01T12:00:00Z".parse().unwrap(),
+        };
+
+        let json = serde_json::to_value(&token).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"token": "ya29.token", "expires_at": "2024-05-01T12:00:00Z"})
+        );
+        assert_eq!(serde_json::from_value::<AccessToken>(json).unwrap(), token);
+    }
+
+    #[test]
+    fn non_bearer_tokens_are_rejected() {
+        let response = TokenResponse {
+            access_token: "token".to_string(),
+            expires_in: Some(3600),
+            token_type: "MAC".to_string(),
+        };
+
+        let error = response.into_access_token(Utc::now()).unwrap_err();
+        assert!(matches!(error, FcmError::TokenProviderError(_)));
+    }
+
+    #[tokio::test]
+    async fn malformed_metadata_server_tokens_keep_the_body() {
+        let server = MockServer::start(|_| Reply::text(200, "<html>proxy error</html>")).await;
+        let provider = MetadataServerTokenProvider {
+            base_url: server.url().to_string(),
+            ..MetadataServerTokenProvider::new()
+        };
+
+        let error = provider.fetch_token().await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            FcmError::ResponseParseError { ref body, .. } if body == "<html>proxy error</html>"
+        ));
+        let request = &server.requests()[0];
+        assert_eq!(request.headers["metadata-flavor"], "Google");
+        assert!(request
+            .path
+            .starts_with("/instance/service-accounts/default/token?scopes="));
+    }
+
+    #[tokio::test]
+    async fn provider_tokens_are_shared_by_every_scope_set() {
+        let provider = provider(chrono::Duration::hours(1));
+        let service =
+            FcmNotification::from_token_provider("test-project", provider.clone()).unwrap();
+
+        let (sending, _) = service.access_token_for(MESSAGING_SCOPES).await.unwrap();
+        let (topics, cached) = service
+            .access_token_for(crate::topics::TOPIC_MANAGEMENT_SCOPES)
+            .await
+            .unwrap();
+
+        assert_eq!(topics, sending);
+        assert!(cached);
+        assert_eq!(provider.fetches.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,355 @@
+//! Subscribing devices to topics and unsubscribing them.
+
+use crate::{FcmApiError, FcmError, FcmNotification, FIREBASE_MESSAGING_SCOPE};
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+
+/// The OAuth2 scopes of the access tokens used for the Instance ID API.
+///
+/// These are the scopes Google's Admin SDKs request for it. With a service account the token is
+/// cached separately from the one used for sending; a [`TokenProvider`](crate::TokenProvider)'s
+/// token is used for both.
+pub(crate) const TOPIC_MANAGEMENT_SCOPES: &[&str] = &[
+    FIREBASE_MESSAGING_SCOPE,
+    "https://www.googleapis.com/auth/cloud-platform",
+];
+
+/// The largest number of tokens the Instance ID API accepts in one request.
+///
+/// Longer token lists are split into requests of this size, which are sent one after the other.
+pub const MAX_TOPIC_MANAGEMENT_TOKENS: usize = 1000;
+
+/// The outcome of subscribing or unsubscribing one device token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicManagementResult {
+    /// The device registration token.
+    pub token: String,
+    /// The error reported for this token, e.g. `NOT_FOUND` or `INVALID_ARGUMENT`, or `None` if
+    /// the token was (un)subscribed.
+    pub error: Option<String>,
+}
+
+impl TopicManagementResult {
+    /// Returns `true` if the token was (un)subscribed.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns `true` if the token is unknown or malformed, so it can be deleted from the
+    /// caller's datastore.
+    pub fn is_invalid_token(&self) -> bool {
+        matches!(
+            self.error.as_deref(),
+            Some("NOT_FOUND" | "INVALID_ARGUMENT")
+        )
+    }
+
+    /// Pairs each of `tokens` with its entry of the `results` array of an Instance ID
+    /// `batchAdd` or `batchRemove` response body.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::ResponseParseError`] if the body is not such a response or doesn't
+    /// hold exactly one result per token.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use fcm_notification::TopicManagementResult;
+    /// let body = r#"{"results":[{},{"error":"NOT_FOUND"},{"error":"INTERNAL"}]}"#;
+    /// let results = TopicManagementResult::from_response(&["a", "b", "c"], body)?;
+    ///
+    /// assert!(results[0].is_success());
+    /// assert_eq!(results[1].token, "b");
+    /// assert!(results[1].is_invalid_token());
+    /// assert_eq!(results[2].error.as_deref(), Some("INTERNAL"));
+    /// assert!(!results[2].is_invalid_token());
+    /// # Ok::<(), fcm_notification::FcmError>(())
+    /// ```
+    pub fn from_response<S: AsRef<str>>(tokens: &[S], body: &str) -> Result<Vec<Self>, FcmError> {
+        #[derive(Deserialize)]
+        struct BatchResponse {
+            results: Vec<Outcome>,
+        }
+
+        #[derive(Deserialize)]
+        struct Outcome {
+            error: Option<String>,
+        }
+
+        let parse_error = |source| FcmError::ResponseParseError {
+            body: body.to_string(),
+            source,
+        };
+        let response: BatchResponse = serde_json::from_str(body).map_err(parse_error)?;
+        if response.results.len() != tokens.len() {
+            return Err(parse_error(serde_json::Error::custom(format!(
+                "expected {} results, got {}",
+                tokens.len(),
+                response.results.len()
+            ))));
+        }
+
+        Ok(tokens
+            .iter()
+            .zip(response.results)
+            .map(|(token, outcome)| Self {
+                token: token.as_ref().to_string(),
+                error: outcome.error,
+            })
+            .collect())
+    }
+}
+
+impl FcmNotification {
+    /// Subscribes each of `tokens` to `topic`.
+    ///
+    /// The outcome of every token is returned in the order of `tokens`; a token FCM doesn't
+    /// know doesn't fail the others. Tokens for which
+    /// [`TopicManagementResult::is_invalid_token`] holds can be pruned like those of failed
+    /// sends. Requests are not retried, and count against
+    /// [`with_max_concurrency`](Self::with_max_concurrency) like sends. With a
+    /// [`TokenProvider`](crate::TokenProvider), its token must also carry the `cloud-platform`
+    /// scope.
+    ///
+    /// # Arguments
+    /// * `topic` - The topic name, with or without the `/topics/` prefix.
+    /// * `tokens` - The device registration tokens to subscribe.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::InvalidArgument`] if the topic name is invalid, and an error if the
+    /// access token cannot be retrieved or a request fails as a whole.
+    pub async fn subscribe_to_topic<S: AsRef<str>>(
+        &self,
+        topic: &str,
+        tokens: &[S],
+    ) -> Result<Vec<TopicManagementResult>, FcmError> {
+        self.manage_topic("batchAdd", topic, tokens).await
+    }
+
+    /// Unsubscribes each of `tokens` from `topic`.
+    ///
+    /// Works like [`subscribe_to_topic`](Self::subscribe_to_topic).
+    ///
+    /// # Arguments
+    /// * `topic` - The topic name, with or without the `/topics/` prefix.
+    /// * `tokens` - The device registration tokens to unsubscribe.
+    ///
+    /// # Errors
+    /// Returns [`FcmError::InvalidArgument`] if the topic name is invalid, and an error if the
+    /// access token cannot be retrieved or a request fails as a whole.
+    pub async fn unsubscribe_from_topic<S: AsRef<str>>(
+        &self,
+        topic: &str,
+        tokens: &[S],
+    ) -> Result<Vec<TopicManagementResult>, FcmError> {
+        self.manage_topic("batchRemove", topic, tokens).await
+    }
+
+    /// Calls the Instance ID `operation` for `tokens`, in chunks of
+    /// [`MAX_TOPIC_MANAGEMENT_TOKENS`].
+    async fn manage_topic<S: AsRef<str>>(
+        &self,
+        operation: &str,
+        topic: &str,
+        tokens: &[S],
+    ) -> Result<Vec<TopicManagementResult>, FcmError> {
+        #[derive(Serialize)]
+        struct BatchRequest<'a> {
+            to: &'a str,
+            registration_tokens: Vec<&'a str>,
+        }
+
+        let name = topic.strip_prefix("/topics/").unwrap_or(topic);
+        crate::condition::check_topic_name(name)?;
+        let to = format!("/topics/{}", name);
+        let url = format!("{}/iid/v1:{}", self.instance_id_url, operation);
+
+        let mut results = Vec::with_capacity(tokens.len());
+        for chunk in tokens.chunks(MAX_TOPIC_MANAGEMENT_TOKENS) {
+            let (access_token, _) = self.access_token_for(TOPIC_MANAGEMENT_SCOPES).await?;
+            let request = BatchRequest {
+                to: &to,
+                registration_tokens: chunk.iter().map(AsRef::as_ref).collect(),
+            };
+            let _permit = self.acquire_send_permit().await;
+            let response = self
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", access_token))
+                // Makes the Instance ID API accept an OAuth2 access token instead of a server key.
+                .header("access_token_auth", "true")
+                .json(&request)
+                .send()
+                .await?;
+
+            let status = response.status();
+            if !status.is_success() {
+                let body = crate::read_capped(response, self.max_error_body_size).await?;
+                return Err(FcmError::ApiError(FcmApiError::from_response(
+                    status.as_u16(),
+                    &body,
+                )));
+            }
+            let body = response.text().await?;
+            results.extend(TopicManagementResult::from_response(chunk, &body)?);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockServer, Reply, Request};
+    use crate::Message;
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Answers Instance ID requests with a success for every token but `unknown-token`.
+    fn instance_id(request: &Request) -> Option<Reply> {
+        if !request.path.starts_with("/iid/v1:") {
+            return None;
+        }
+        let results: Vec<_> = request.json()["registration_tokens"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|token| match token.as_str() {
+                Some("unknown-token") => json!({"error": "NOT_FOUND"}),
+                _ => json!({}),
+            })
+            .collect();
+        Some(Reply::json(200, json!({ "results": results })))
+    }
+
+    #[test]
+    fn instance_id_results_are_paired_with_their_tokens() {
+        // The shape of a `batchAdd` response for four tokens, two of them rejected.
+        let body = r#"{
+            "results": [
+                {},
+                {"error": "NOT_FOUND"},
+                {},
+                {"error": "INVALID_ARGUMENT"}
+            ]
+        }"#;
+        let tokens = ["token-a", "token-b", "token-c", "token-d"];
+
+        let results = TopicManagementResult::from_response(&tokens, body).unwrap();
+
+        let invalid: Vec<&str> = results
+            .iter()
+            .filter(|result| result.is_invalid_token())
+            .map(|result| result.token.as_str())
+            .collect();
+        assert_eq!(invalid, ["token-b", "token-d"]);
+        assert!(results[0].is_success());
+        assert!(results[2].is_success());
+        assert_eq!(results[1].error.as_deref(), Some("NOT_FOUND"));
+    }
+
+    #[test]
+    fn result_count_must_match_the_tokens() {
+        let body = r#"{"results": [{}]}"#;
+
+        let error =
+            TopicManagementResult::from_response(&["token-a", "token-b"], body).unwrap_err();
+
+        assert!(matches!(error, FcmError::ResponseParseError { .. }));
+    }
+
+    #[tokio::test]
+    async fn subscribing_reports_each_token() {
+        let server = MockServer::fcm_with(instance_id).await;
+        let service = server.service();
+
+        let results = service
+            .subscribe_to_topic("/topics/news", &["device-token", "unknown-token"])
+            .await
+            .unwrap();
+
+        assert!(results[0].is_success());
+        assert_eq!(results[1].token, "unknown-token");
+        assert!(results[1].is_invalid_token());
+
+        let request = &server.requests()[1];
+        assert_eq!(request.path, "/iid/v1:batchAdd");
+        assert_eq!(request.headers["access_token_auth"], "true");
+        assert_eq!(request.json()["to"], "/topics/news");
+    }
+
+    #[tokio::test]
+    async fn long_token_lists_are_split() {
+        let server = MockServer::fcm_with(instance_id).await;
+        let service = server.service();
+        let tokens: Vec<String> = (0..1001).map(|i| format!("token-{}", i)).collect();
+
+        let results = service
+            .unsubscribe_from_topic("news", &tokens)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 1001);
+        assert_eq!(results[1000].token, "token-1000");
+        let requests: Vec<Request> = server
+            .requests()
+            .into_iter()
+            .filter(|request| request.path == "/iid/v1:batchRemove")
+            .collect();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].json()["registration_tokens"],
+            json!(["token-1000"])
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_topic_names_are_rejected_locally() {
+        let server = MockServer::fcm_with(instance_id).await;
+
+        let error = server
+            .service()
+            .subscribe_to_topic("news feed", &["device-token"])
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, FcmError::InvalidArgument(_)));
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn topic_requests_wait_for_the_send_limit() {
+        let server = MockServer::fcm_with(|request| {
+            instance_id(request)
+                .or_else(|| Some(Reply::sent(0).delayed(Duration::from_millis(200))))
+        })
+        .await;
+        let service = server.service().with_max_concurrency(1);
+
+        let send = tokio::spawn({
+            let service = service.clone();
+            async move {
+                service
+                    .send(&Message::notification("device-token", "Hello", "World"))
+                    .await
+            }
+        });
+        while server.sends().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        let subscribe = tokio::spawn({
+            let service = service.clone();
+            async move { service.subscribe_to_topic("news", &["device-token"]).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let subscribed = |server: &MockServer| {
+            server
+                .requests()
+                .iter()
+                .any(|request| request.path == "/iid/v1:batchAdd")
+        };
+        assert!(!subscribed(&server));
+
+        send.await.unwrap().unwrap();
+        subscribe.await.unwrap().unwrap();
+        assert!(subscribed(&server));
+    }
+}
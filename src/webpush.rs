@@ -0,0 +1,99 @@
+//! WebPush-specific options for messages delivered to browsers.
+
+use crate::ValidationError;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// The maximum number of notification actions most browsers display.
+pub const MAX_WEBPUSH_ACTIONS: usize = 2;
+
+/// The longest WebPush `Topic` header the push services accept, in characters.
+pub const MAX_WEBPUSH_TOPIC_LEN: usize = 32;
+
+/// Options applied to messages delivered through the WebPush protocol.
+///
+/// Serialized as `message.webpush`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WebpushConfig {
+    /// WebPush protocol headers, e.g. `Urgency` or `TTL`.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Web Notification options.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification: Option<WebpushNotification>,
+    /// FCM options for WebPush.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fcm_options: Option<WebpushFcmOptions>,
+}
+
+impl WebpushConfig {
+    /// Collects the mistakes push services would reject in these options.
+    pub(crate) fn validation_errors(&self, errors: &mut Vec<ValidationError>) {
+        if let Some(topic) = self.headers.get("Topic") {
+            let url_safe = topic
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+            if topic.is_empty() || topic.len() > MAX_WEBPUSH_TOPIC_LEN || !url_safe {
+                errors.push(ValidationError::new(
+                    "webpush.headers.Topic",
+                    format!(
+                        "WebPush Topic `{}` must be 1 to {} characters of the URL-safe base64 \
+                         alphabet [A-Za-z0-9-_]",
+                        topic, MAX_WEBPUSH_TOPIC_LEN
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// FCM-specific options for WebPush messages.
+///
+/// Serialized as `message.webpush.fcm_options`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WebpushFcmOptions {
+    /// The page opened when the user clicks the notification. Must be an `https://` URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+/// Options of the Web Notification shown by the browser.
+///
+/// Serialized as `message.webpush.notification`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WebpushNotification {
+    /// Buttons shown on the notification.
+    ///
+    /// Browsers display at most [`MAX_WEBPUSH_ACTIONS`] of them, and sending more is rejected
+    /// locally. Android has no equivalent in FCM: to offer actions there, send them in `data`
+    /// and build the buttons in the app.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<NotificationAction>,
+    /// Any other [Web Notification options](https://developer.mozilla.org/docs/Web/API/Notification/Notification),
+    /// such as `icon` or `requireInteraction`.
+    #[serde(flatten)]
+    pub custom: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A button on a web notification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NotificationAction {
+    /// The identifier reported to the service worker when the button is clicked.
+    pub action: String,
+    /// The label of the button.
+    pub title: String,
+    /// An optional URL of an icon shown on the button.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+impl NotificationAction {
+    /// Creates an action without an icon.
+    pub fn new(action: &str, title: &str) -> Self {
+        Self {
+            action: action.to_string(),
+            title: title.to_string(),
+            icon: None,
+        }
+    }
+}